@@ -1,6 +1,19 @@
 use std::rc::Rc;
 
-use mdp_rs::{mdp::MdpBuilder, model::SingleActionBuilder, solver::ValueIterationSolver};
+use mdp_rs::{
+    mdp::MdpBuilder,
+    model::SingleActionBuilder,
+    planner::UctPlanner,
+    rng::Rng,
+    solver::{
+        LaoStarSolver, LrtdpSolver, PolicyIterationSolver, PrioritizedSweepingSolver, RtdpSolver,
+        Solver, StochasticShortestPathSolver, SweepOrder, TopologicalSolver, UpdateMode,
+        ValueIterationSolver,
+    },
+};
+
+// How close every solver has to get to value iteration.
+const TOLERANCE: f64 = 1e-3;
 
 #[derive(Debug, Hash)]
 struct GoForward(Vec<Person>);
@@ -46,7 +59,7 @@ fn main() {
             SingleActionBuilder::<WorldState, GoForward>::new(GoForward(vec![p1.clone()]))
                 .precondition(Rc::new(move |s| !s.is_finished()))
                 .precondition(Rc::new(|s| s.flashlight_at_start))
-                .precondition(Rc::new(move |s| s.at_start.contains(&p1)))
+                .precondition(Rc::new(move |s| s.at_start.contains(p1)))
                 .outcome(Rc::new(move |s, r| {
                     s.at_start.retain(|p| p != p1);
                     s.at_end.push(p1.clone());
@@ -59,7 +72,7 @@ fn main() {
             SingleActionBuilder::<WorldState, GoBackward>::new(GoBackward(vec![p1.clone()]))
                 .precondition(Rc::new(move |s| !s.is_finished()))
                 .precondition(Rc::new(|s| !s.flashlight_at_start))
-                .precondition(Rc::new(move |s| s.at_end.contains(&p1)))
+                .precondition(Rc::new(move |s| s.at_end.contains(p1)))
                 .outcome(Rc::new(move |s, r| {
                     s.at_end.retain(|p| p != p1);
                     s.at_start.push(p1.clone());
@@ -80,7 +93,7 @@ fn main() {
                 .precondition(Rc::new(move |s| !s.is_finished()))
                 .precondition(Rc::new(|s| s.flashlight_at_start))
                 .precondition(Rc::new(move |s| {
-                    s.at_start.contains(&p1) && s.at_start.contains(&p2)
+                    s.at_start.contains(p1) && s.at_start.contains(p2)
                 }))
                 .outcome(Rc::new(move |s, r| {
                     s.at_start.retain(|p| p != p1 && p != p2);
@@ -99,7 +112,7 @@ fn main() {
                 .precondition(Rc::new(move |s| !s.is_finished()))
                 .precondition(Rc::new(|s| !s.flashlight_at_start))
                 .precondition(Rc::new(move |s| {
-                    s.at_end.contains(&p1) && s.at_end.contains(&p2)
+                    s.at_end.contains(p1) && s.at_end.contains(p2)
                 }))
                 .outcome(Rc::new(move |s, r| {
                     s.at_end.retain(|p| p != p1 && p != p2);
@@ -113,15 +126,121 @@ fn main() {
         }
     }
 
+    let actions = mdp.actions();
+    let mut planner = UctPlanner::new(mdp.actions(), 1.0)
+        .exploration(50.0)
+        .max_iterations(20000)
+        .max_depth(30)
+        .seed(1);
     let mdp = mdp.build();
 
     // mdp.print();
 
     // Solve the MDP with a Value Iteration Solver
-    let mut solver = ValueIterationSolver::new(&mdp, 1.0);
-    solver.solve();
-    let policy = solver.get_policy();
-    // policy.print(&mdp, solver.values());
+    let solution = ValueIterationSolver::new(&mdp, 1.0).solve().unwrap();
+    let policy = solution.policy();
+
+    // The other solvers should agree with Value Iteration
+    let solvers: Vec<(&str, Box<dyn Solver<WorldState>>)> = vec![
+        (
+            "Policy Iteration",
+            Box::new(PolicyIterationSolver::new(&mdp, 1.0)),
+        ),
+        (
+            "Gauss-Seidel",
+            Box::new(
+                ValueIterationSolver::new(&mdp, 1.0)
+                    .update_mode(UpdateMode::GaussSeidel)
+                    .sweep_order(SweepOrder::ReverseTopological),
+            ),
+        ),
+        ("Topological", Box::new(TopologicalSolver::new(&mdp, 1.0))),
+        (
+            "Prioritized Sweeping",
+            Box::new(PrioritizedSweepingSolver::new(&mdp, 1.0)),
+        ),
+        (
+            "Shortest Path",
+            Box::new(StochasticShortestPathSolver::new(&mdp, |s| s.is_finished())),
+        ),
+    ];
+    for (name, mut solver) in solvers {
+        let other = solver.solve().unwrap();
+        println!(
+            "{}: {} iterations (instead of {}), max value difference {:.6}",
+            name,
+            other.iterations(),
+            solution.iterations(),
+            solution.max_value_difference(&other)
+        );
+        assert!(
+            solution.max_value_difference(&other) < TOLERANCE,
+            "{} disagrees with value iteration",
+            name
+        );
+    }
+
+    // RTDP and LRTDP only back up states reachable from the start under good policies.
+    // Every crossing takes time, so 0 is an admissible heuristic.
+    let partial: Vec<(&str, Box<dyn Solver<WorldState>>)> = vec![
+        ("RTDP", Box::new(RtdpSolver::new(&mdp, 1.0, |_| 0.0))),
+        ("LRTDP", Box::new(LrtdpSolver::new(&mdp, 1.0, |_| 0.0))),
+    ];
+    for (name, mut solver) in partial {
+        let other = solver.solve().unwrap();
+        println!(
+            "{}: {} trials, {} of {} states covered, initial value {:.6} (instead of {:.6})",
+            name,
+            other.iterations(),
+            other.policy().actions().iter().flatten().count(),
+            mdp.states().len(),
+            other.values()[0],
+            solution.values()[0]
+        );
+        assert!(
+            (other.values()[0] - solution.values()[0]).abs() < TOLERANCE,
+            "{} disagrees with value iteration",
+            name
+        );
+    }
+
+    // LAO* doesn't even need the `Mdp`, it expands states from the actions as it goes.
+    let mut lao_star = LaoStarSolver::new(initial_state.clone(), actions, 1.0, |_| 0.0);
+    let other = lao_star.solve().unwrap();
+    println!(
+        "LAO*: {} iterations, {} of {} states expanded, initial value {:.6} (instead of {:.6})",
+        other.iterations(),
+        lao_star.expanded_states(),
+        mdp.states().len(),
+        other.values()[0],
+        solution.values()[0]
+    );
+    assert!(
+        (other.values()[0] - solution.values()[0]).abs() < TOLERANCE,
+        "LAO* disagrees with value iteration"
+    );
+
+    // UCT plans online from whatever state we are in, by sampling the actions directly.
+    let mut rng = Rng::new(0);
+    let mut state = initial_state.clone();
+    let mut uct_total = 0.0;
+    while !state.is_finished() {
+        let plan = planner.plan(&state);
+        let action = planner
+            .actions()
+            .iter()
+            .find(|a| Some(&a.action()) == plan.action())
+            .unwrap();
+        let result = action.sample(&state, &mut rng).unwrap();
+        uct_total += result.reward();
+        state = result.state().clone();
+    }
+    println!(
+        "UCT: total reward {:.6} acting online (instead of {:.6})",
+        uct_total,
+        solution.values()[0]
+    );
+    // policy.print(&mdp, solution.values());
 
     let mut current_state = initial_state.clone();
     let mut total_time = 0;
@@ -0,0 +1,117 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    learning::{
+        Agent, DoubleQLearningAgent, ExpectedSarsaAgent, Exploration, LearningConfig, LearningRate,
+        QLearningAgent, SarsaAgent, Simulator,
+    },
+    mdp::MdpBuilder,
+    model::{GrounableAction, GroundingActionBuilder},
+    solver::{Solver, ValueIterationSolver},
+};
+
+// Cliff walking from Sutton and Barto (Example 6.6)
+const WORLD_WIDTH: isize = 12;
+const WORLD_HEIGHT: isize = 4;
+const EPISODES: usize = 500;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Position {
+    x: isize,
+    y: isize,
+}
+
+const START: Position = Position { x: 0, y: 0 };
+const GOAL: Position = Position {
+    x: WORLD_WIDTH - 1,
+    y: 0,
+};
+
+impl Position {
+    fn is_cliff(&self) -> bool {
+        self.y == 0 && self.x > 0 && self.x < WORLD_WIDTH - 1
+    }
+}
+
+#[derive(Hash, Debug)]
+enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+use Direction::*;
+
+#[derive(Hash, Debug)]
+struct Walk(Direction);
+
+impl GrounableAction for Walk {
+    fn enumerate() -> Vec<Self> {
+        vec![Walk(Up), Walk(Down), Walk(Left), Walk(Right)]
+    }
+}
+
+fn main() {
+    let mdp = MdpBuilder::new(START)
+        .add_action(Box::new(
+            GroundingActionBuilder::<Position, Walk>::new()
+                .precondition(Rc::new(|_| Rc::new(|pos| *pos != GOAL)))
+                .outcome(Rc::new(|action| {
+                    Rc::new(move |pos, reward| {
+                        match action.0 {
+                            Up => pos.y = (pos.y + 1).min(WORLD_HEIGHT - 1),
+                            Down => pos.y = (pos.y - 1).max(0),
+                            Left => pos.x = (pos.x - 1).max(0),
+                            Right => pos.x = (pos.x + 1).min(WORLD_WIDTH - 1),
+                        }
+                        *reward = -1.0;
+                        if pos.is_cliff() {
+                            *reward = -100.0;
+                            *pos = START;
+                        }
+                        1.0
+                    })
+                })),
+        ))
+        .build();
+
+    let solution = ValueIterationSolver::new(&mdp, 1.0).solve().unwrap();
+    println!("Optimal return: {:.1}\n", solution.values()[0]);
+
+    let config = LearningConfig::default()
+        .exploration(Exploration::EpsilonGreedy(0.1))
+        .learning_rate(LearningRate::Constant(0.5))
+        .seed(1);
+    let agents: Vec<(&str, Box<dyn Agent>)> = vec![
+        ("SARSA", Box::new(SarsaAgent::new(1.0).with_config(config))),
+        (
+            "Expected SARSA",
+            Box::new(ExpectedSarsaAgent::new(1.0).with_config(config)),
+        ),
+        (
+            "Q-learning",
+            Box::new(QLearningAgent::new(1.0).with_config(config)),
+        ),
+        (
+            "Double Q-learning",
+            Box::new(DoubleQLearningAgent::new(1.0).with_config(config)),
+        ),
+    ];
+
+    // The on-policy methods learn the safe path and get more reward while exploring. Q-learning
+    // learns the optimal path along the cliff, but falls off it now and then.
+    for (name, mut agent) in agents {
+        let mut simulator = Simulator::new(&mdp).seed(2);
+        let returns = agent.train(&mut simulator, EPISODES);
+        let averages = returns
+            .chunks(EPISODES / 10)
+            .map(|chunk| format!("{:.1}", chunk.iter().sum::<f64>() / chunk.len() as f64))
+            .collect::<Vec<_>>();
+        println!("{}: average return per {} episodes", name, EPISODES / 10);
+        println!("  {}", averages.join(", "));
+        println!(
+            "  learned value of the start {:.1}\n",
+            agent.q_table().values()[0]
+        );
+    }
+}
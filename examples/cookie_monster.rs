@@ -1,6 +1,24 @@
 use std::rc::Rc;
 
-use mdp_rs::{mdp::MdpBuilder, model::SingleActionBuilder, solver::ValueIterationSolver};
+use mdp_rs::{
+    constrained::{
+        ConstrainedMdpBuilder, ConstrainedMethod, ConstrainedSolver, CostingActionBuilder,
+    },
+    learning::{
+        Agent, DynaQAgent, Exploration, ImportanceSampling, LearningConfig, LearningRate,
+        MonteCarloControlAgent, MonteCarloPrediction, QLearningAgent, Simulator, Visits,
+    },
+    model::SingleActionBuilder,
+    policy::{Policy, StochasticPolicy},
+    solver::{
+        AverageRewardSolver, EvaluationMethod, LpSolver, PolicyEvaluator, PolicyIterationSolver,
+        PrioritizedSweepingSolver, Solver, SweepOrder, TopologicalSolver, UpdateMode,
+        ValueIterationSolver,
+    },
+};
+
+// How close every solver has to get to value iteration.
+const TOLERANCE: f64 = 1e-3;
 
 // World state definition
 #[derive(Hash, PartialEq, Eq, Clone, Debug)]
@@ -28,8 +46,9 @@ fn main() {
         visits: 0,
     };
 
-    // Create mdp and add actions + preconditions + effects to the mdp
-    let mdp = MdpBuilder::new(initial_state)
+    // Create mdp and add actions + preconditions + effects to the mdp. Robbing the bakery
+    // risks getting banned, and the vending machine can jam, which are tracked as costs
+    let cookies = ConstrainedMdpBuilder::new(initial_state)
         .add_action(Box::new(
             SingleActionBuilder::<State, VisitBakery>::new(VisitBakery)
                 .precondition(Rc::new(|s| !s.banned))
@@ -43,31 +62,33 @@ fn main() {
                 })),
         ))
         .add_action(Box::new(
-            SingleActionBuilder::<State, RobBakery>::new(RobBakery)
+            CostingActionBuilder::<State, RobBakery>::new(RobBakery)
                 .precondition(Rc::new(|s| !s.banned))
-                .outcome(Rc::new(|state, reward| {
+                .outcome(Rc::new(|state, reward, _costs| {
                     if state.visits != 1 {
                         state.visits += 1
                     }
                     *reward = 5.0;
                     0.85
                 }))
-                .outcome(Rc::new(|state, _reward| {
+                .outcome(Rc::new(|state, _reward, costs| {
                     state.banned = true;
                     state.visits = 0;
+                    costs.push(("banned", 1.0));
                     0.15
                 })),
         ))
         .add_action(Box::new(
-            SingleActionBuilder::<State, VendingMachine>::new(VendingMachine)
+            CostingActionBuilder::<State, VendingMachine>::new(VendingMachine)
                 .precondition(Rc::new(|s| !s.jammed))
-                .outcome(Rc::new(|_s, reward| {
+                .outcome(Rc::new(|_s, reward, _costs| {
                     *reward = 1.0;
                     0.5
                 }))
-                .outcome(Rc::new(|state, reward| {
+                .outcome(Rc::new(|state, reward, costs| {
                     *reward = 3.0;
                     state.jammed = true;
+                    costs.push(("jams", 1.0));
                     0.5
                 })),
         ))
@@ -80,12 +101,283 @@ fn main() {
                 })),
         ))
         .build();
+    let mdp = cookies.mdp();
 
     mdp.print();
 
     // Solve the MDP with a Value Iteration Solver
-    let mut solver = ValueIterationSolver::new(&mdp, 0.94);
-    solver.solve();
-    let policy = solver.get_policy();
-    policy.print(&mdp, solver.values());
+    let solution = ValueIterationSolver::new(mdp, 0.94).solve().unwrap();
+    let policy = solution.policy();
+
+    // The other solvers should agree with Value Iteration
+    let solvers: Vec<(&str, Box<dyn Solver<State>>)> = vec![
+        (
+            "Policy Iteration",
+            Box::new(PolicyIterationSolver::new(mdp, 0.94)),
+        ),
+        (
+            "Gauss-Seidel",
+            Box::new(
+                ValueIterationSolver::new(mdp, 0.94)
+                    .update_mode(UpdateMode::GaussSeidel)
+                    .sweep_order(SweepOrder::ReverseTopological),
+            ),
+        ),
+        ("Topological", Box::new(TopologicalSolver::new(mdp, 0.94))),
+        (
+            "Prioritized Sweeping",
+            Box::new(PrioritizedSweepingSolver::new(mdp, 0.94)),
+        ),
+        ("Linear Programming", Box::new(LpSolver::new(mdp, 0.94))),
+    ];
+    for (name, mut solver) in solvers {
+        let other = solver.solve().unwrap();
+        println!(
+            "{}: {} iterations (instead of {}), max value difference {:.6}",
+            name,
+            other.iterations(),
+            solution.iterations(),
+            solution.max_value_difference(&other)
+        );
+        assert!(
+            solution.max_value_difference(&other) < TOLERANCE,
+            "{} disagrees with value iteration",
+            name
+        );
+    }
+    policy.print(mdp, solution.values());
+
+    // Hand-written policies: an honest monster that goes to the bakery whenever it can and
+    // never robs it, and one that picks uniformly at random
+    let honest = Policy::new(
+        (0..mdp.states().len())
+            .map(|i| {
+                ["VisitBakery", "VendingMachine", "Wait"]
+                    .iter()
+                    .find_map(|name| mdp.actions(i).keys().find(|a| a.to_string() == *name))
+                    .cloned()
+            })
+            .collect(),
+    );
+    let uniform = StochasticPolicy::uniform(mdp);
+    for method in [EvaluationMethod::Exact, EvaluationMethod::Iterative] {
+        let evaluator = PolicyEvaluator::new(mdp, 0.94).method(method);
+        let honest = evaluator.evaluate(&honest).unwrap();
+        let uniform = evaluator.evaluate_stochastic(&uniform).unwrap();
+        println!(
+            "{:?} evaluation of the initial state: optimal {:.3}, honest {:.3}, uniform {:.3} ({} iterations)",
+            method,
+            solution.values()[0],
+            honest.values()[0],
+            uniform.values()[0],
+            uniform.iterations()
+        );
+    }
+    println!();
+
+    // The optimal monster robs the bakery and risks getting banned. Cap the (discounted)
+    // chance of that, and see what it costs in cookies
+    let banned = cookies.index_of_cost("banned").unwrap();
+    let optimal_costs = cookies.cost_values(&StochasticPolicy::from(policy), 0.94);
+    println!(
+        "Unconstrained: worth {:.3}, banned with discounted probability {:.3}",
+        solution.values()[0],
+        optimal_costs[banned][0]
+    );
+    let mut constrained_rewards = vec![];
+    for (name, method) in [
+        ("Occupancy LP", ConstrainedMethod::OccupancyLp),
+        (
+            "Lagrangian",
+            ConstrainedMethod::Lagrangian {
+                step_size: 50.0,
+                rounds: 100,
+            },
+        ),
+    ] {
+        let constrained = ConstrainedSolver::new(&cookies, 0.94)
+            .budget("banned", 0.1)
+            .method(method)
+            .solve()
+            .unwrap();
+        let costs = cookies
+            .cost_names()
+            .iter()
+            .zip(constrained.costs())
+            .map(|(name, cost)| format!("{} {:.3}", name, cost))
+            .collect::<Vec<_>>()
+            .join(", ");
+        constrained_rewards.push(constrained.reward());
+        println!(
+            "{} with a budget of 0.1: worth {:.3}, costs {}, price of the ban {:.3}",
+            name,
+            constrained.reward(),
+            costs,
+            constrained.multipliers()[banned]
+        );
+        let actions = constrained
+            .policy()
+            .distribution(0)
+            .iter()
+            .map(|(action, p)| format!("{} {:.3}", action, p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!("  initial state: {}", actions);
+    }
+    // Mixing the Lagrangian policies meets the budget exactly, like the LP.
+    assert!(
+        (constrained_rewards[0] - constrained_rewards[1]).abs() < TOLERANCE,
+        "the Lagrangian disagrees with the occupancy LP"
+    );
+    println!();
+
+    // Q-learning only gets to see sampled rewards and next states
+    let mut simulator = Simulator::new(mdp).seed(1);
+    let mut agent = QLearningAgent::new(0.94).with_config(
+        LearningConfig::default()
+            .exploration(Exploration::EpsilonGreedy(0.2))
+            .learning_rate(LearningRate::Polynomial(0.7))
+            .max_steps(200),
+    );
+    agent.train(&mut simulator, 2000);
+    let learned = agent.policy();
+    let agreeing = (0..mdp.states().len())
+        .filter(|&i| learned.get_action(i) == policy.get_action(i))
+        .count();
+    let values = agent.q_table().values();
+    let difference = values
+        .iter()
+        .zip(solution.values())
+        .map(|(a, b)| (a - b).abs())
+        .fold(0.0, f64::max);
+    println!(
+        "Q-learning: same action in {} of {} states, max value difference {:.3}\n",
+        agreeing,
+        mdp.states().len(),
+        difference
+    );
+
+    // What the agent would get if it kept exploring with a softmax over its Q-values
+    let softmax = agent
+        .q_table()
+        .exploring_policy(Exploration::Boltzmann(1.0));
+    let softmax_values = PolicyEvaluator::new(mdp, 0.94)
+        .evaluate_stochastic(&softmax)
+        .unwrap();
+    let greedy_values = PolicyEvaluator::new(mdp, 0.94)
+        .evaluate(&softmax.greedy())
+        .unwrap();
+    println!(
+        "Q-learning softmax policy: worth {:.3} in the initial state, {:.3} when greedy\n",
+        softmax_values.values()[0],
+        greedy_values.values()[0]
+    );
+
+    // Monte Carlo estimates of the values of the optimal policy, on and off policy. Exploring
+    // starts reach the states the policy never visits from the initial state. The task never
+    // ends, so only the first steps of every episode have a long enough return to count.
+    let estimates = vec![
+        (
+            "first-visit",
+            MonteCarloPrediction::new(0.94)
+                .exploring_starts(true)
+                .max_steps(130)
+                .evaluate(&mut simulator, policy, 1000),
+        ),
+        (
+            "every-visit",
+            MonteCarloPrediction::new(0.94)
+                .visits(Visits::Every)
+                .exploring_starts(true)
+                .max_steps(130)
+                .evaluate(&mut simulator, policy, 1000),
+        ),
+        (
+            "ordinary importance sampling",
+            MonteCarloPrediction::new(0.94)
+                .importance_sampling(ImportanceSampling::Ordinary)
+                .exploring_starts(true)
+                .max_steps(130)
+                .evaluate_off_policy(&mut simulator, policy, policy, 0.1, 1000),
+        ),
+        (
+            "weighted importance sampling",
+            MonteCarloPrediction::new(0.94)
+                .importance_sampling(ImportanceSampling::Weighted)
+                .exploring_starts(true)
+                .max_steps(130)
+                .evaluate_off_policy(&mut simulator, policy, policy, 0.1, 1000),
+        ),
+    ];
+    for (name, values) in estimates {
+        let difference = values
+            .iter()
+            .zip(solution.values())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        println!(
+            "Monte Carlo ({}): max value difference {:.3}",
+            name, difference
+        );
+    }
+
+    let mut agent = MonteCarloControlAgent::new(0.94)
+        .exploring_starts(true)
+        .with_config(
+            LearningConfig::default()
+                .learning_rate(LearningRate::Polynomial(1.0))
+                .max_steps(100),
+        );
+    agent.train(&mut simulator, 2000);
+    let learned = agent.policy();
+    let agreeing = (0..mdp.states().len())
+        .filter(|&i| learned.get_action(i) == policy.get_action(i))
+        .count();
+    println!(
+        "Monte Carlo control (exploring starts): same action in {} of {} states\n",
+        agreeing,
+        mdp.states().len()
+    );
+
+    // Dyna-Q learns a model of the bakery while it plays, which value iteration can solve
+    let mut agent = DynaQAgent::new(0.94).planning_steps(10).with_config(
+        LearningConfig::default()
+            .exploration(Exploration::EpsilonGreedy(0.2))
+            .max_steps(200),
+    );
+    agent.train(&mut simulator, 2000);
+    let learned_mdp = agent.learned_mdp(mdp.states());
+    let learned_solution = ValueIterationSolver::new(&learned_mdp, 0.94)
+        .solve()
+        .unwrap();
+    let agreeing = (0..mdp.states().len())
+        .filter(|&i| learned_solution.policy().get_action(i) == policy.get_action(i))
+        .count();
+    println!(
+        "Dyna-Q: tried {} state-action pairs, solving its learned model gives the same action in {} of {} states, max value difference {:.3}\n",
+        agent.known_pairs(),
+        agreeing,
+        mdp.states().len(),
+        solution.max_value_difference(&learned_solution)
+    );
+
+    // The bakery loop never ends, so also look at the long run cookies per step
+    let mut average_solver = AverageRewardSolver::new(mdp);
+    let average_solution = average_solver.solve().unwrap();
+    println!("================ Average Reward ================\n");
+    println!(
+        "{} recurrent classes (unichain: {})\n",
+        average_solver.recurrent_classes().len(),
+        average_solver.is_unichain()
+    );
+    for (i, state) in mdp.states().iter().enumerate() {
+        println!("State {} {:?}", i, state);
+        println!(
+            "  - Action:      {:?}",
+            average_solution.policy().get_action(i)
+        );
+        println!("  - Gain:        {:.3}", average_solver.gain()[i]);
+        println!("  - Bias:        {:.3}", average_solver.bias()[i]);
+        println!()
+    }
 }
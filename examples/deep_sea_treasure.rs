@@ -0,0 +1,123 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    mdp::MdpBuilder,
+    model::VectorActionBuilder,
+    multi_objective::{ConvexCoverageSetSolver, ParetoFrontSolver, ParetoPolicy},
+    solver::{Solver, ValueIterationSolver},
+};
+
+// Deep Sea Treasure from Vamplew et al. (2011)
+// A submarine starts in the top left corner of a 10 column grid and searches for treasure on
+// the seabed. The further away a treasure is, the more it is worth. Every step costs 1 unit of
+// time, so the objectives are [treasure, -time]. Several of the trade-offs are in a concave
+// part of the Pareto front, so no weighting of the objectives ever picks them.
+const DISCOUNT: f64 = 0.99;
+// Depth and value of the treasure in every column
+const TREASURES: [(isize, f64); 10] = [
+    (1, 1.0),
+    (2, 2.0),
+    (3, 3.0),
+    (4, 5.0),
+    (4, 8.0),
+    (4, 16.0),
+    (7, 24.0),
+    (7, 50.0),
+    (9, 74.0),
+    (10, 124.0),
+];
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Submarine {
+    row: isize,
+    column: isize,
+}
+
+impl Submarine {
+    fn treasure(&self) -> Option<f64> {
+        let (depth, value) = TREASURES[self.column as usize];
+        (self.row == depth).then_some(value)
+    }
+}
+
+#[derive(Clone, Copy, Hash, Debug)]
+enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+use Move::*;
+
+fn sail(direction: Move) -> VectorActionBuilder<Submarine, Move> {
+    VectorActionBuilder::new(direction, 2)
+        .precondition(Rc::new(|submarine: &Submarine| {
+            submarine.treasure().is_none()
+        }))
+        .outcome(Rc::new(move |submarine, rewards| {
+            let (row, column) = match direction {
+                Up => (submarine.row - 1, submarine.column),
+                Down => (submarine.row + 1, submarine.column),
+                Left => (submarine.row, submarine.column - 1),
+                Right => (submarine.row, submarine.column + 1),
+            };
+            // Stay put when sailing into the seabed or off the map
+            if (0..10).contains(&column) && row >= 0 && row <= TREASURES[column as usize].0 {
+                submarine.row = row;
+                submarine.column = column;
+            }
+            rewards[0] = submarine.treasure().unwrap_or(0.0);
+            rewards[1] = -1.0;
+            1.0
+        }))
+}
+
+fn print(name: &str, policies: &[ParetoPolicy]) {
+    println!("{}: {} policies", name, policies.len());
+    for policy in policies {
+        println!(
+            "  treasure {:>7.3}, time {:>8.3}",
+            policy.value()[0],
+            policy.value()[1]
+        );
+    }
+    println!();
+}
+
+fn main() {
+    let mdp = MdpBuilder::new(Submarine { row: 0, column: 0 })
+        .add_action(Box::new(sail(Up)))
+        .add_action(Box::new(sail(Down)))
+        .add_action(Box::new(sail(Left)))
+        .add_action(Box::new(sail(Right)))
+        .build();
+    println!(
+        "{} states with {} objectives\n",
+        mdp.states().len(),
+        mdp.objectives()
+    );
+
+    // One weighting at a time: plain value iteration on the scalarised model
+    for weights in [[1.0, 0.0], [0.5, 0.5], [0.1, 0.9]] {
+        let solution = ValueIterationSolver::new(&mdp.scalarised(&weights), DISCOUNT)
+            .solve()
+            .unwrap();
+        println!(
+            "Weights {:?}: worth {:.3} in the initial state",
+            weights,
+            solution.values()[0]
+        );
+    }
+    println!();
+
+    // Every weighting at once
+    let mut ccs_solver = ConvexCoverageSetSolver::new(&mdp, DISCOUNT);
+    let coverage_set = ccs_solver.solve().unwrap();
+    println!("Solved {} weightings", ccs_solver.weights().len());
+    print("Convex coverage set", &coverage_set);
+
+    let mut pareto_solver = ParetoFrontSolver::new(&mdp, DISCOUNT);
+    let front = pareto_solver.solve().unwrap();
+    println!("Bounded {} partial policies", pareto_solver.nodes());
+    print("Pareto front", &front);
+}
@@ -1,6 +1,16 @@
 use std::rc::Rc;
 
-use mdp_rs::{mdp::MdpBuilder, model::SingleActionBuilder, solver::ValueIterationSolver};
+use mdp_rs::{
+    mdp::MdpBuilder,
+    model::SingleActionBuilder,
+    solver::{
+        LrtdpSolver, PolicyIterationSolver, PrioritizedSweepingSolver, Solver, SweepOrder,
+        TopologicalSolver, UpdateMode, ValueIterationSolver,
+    },
+};
+
+// How close every solver has to get to value iteration.
+const TOLERANCE: f64 = 1e-3;
 
 const NUM_TURNS: isize = 100;
 const DICE: isize = 20;
@@ -49,10 +59,63 @@ fn main() {
     mdp.print();
 
     // Solve the MDP with a Value Iteration Solver
-    let mut solver = ValueIterationSolver::new(&mdp, 1.0);
-    solver.solve();
-    let policy = solver.get_policy();
-    policy.print(&mdp, solver.values());
+    let solution = ValueIterationSolver::new(&mdp, 1.0).solve().unwrap();
+    let policy = solution.policy();
+
+    // The other solvers should agree with Value Iteration
+    let solvers: Vec<(&str, Box<dyn Solver<WorldState>>)> = vec![
+        (
+            "Policy Iteration",
+            Box::new(PolicyIterationSolver::new(&mdp, 1.0)),
+        ),
+        (
+            "Gauss-Seidel",
+            Box::new(
+                ValueIterationSolver::new(&mdp, 1.0)
+                    .update_mode(UpdateMode::GaussSeidel)
+                    .sweep_order(SweepOrder::ReverseTopological),
+            ),
+        ),
+        ("Topological", Box::new(TopologicalSolver::new(&mdp, 1.0))),
+        (
+            "Prioritized Sweeping",
+            Box::new(PrioritizedSweepingSolver::new(&mdp, 1.0)),
+        ),
+    ];
+    for (name, mut solver) in solvers {
+        let other = solver.solve().unwrap();
+        println!(
+            "{}: {} iterations (instead of {}), max value difference {:.6}",
+            name,
+            other.iterations(),
+            solution.iterations(),
+            solution.max_value_difference(&other)
+        );
+        assert!(
+            solution.max_value_difference(&other) < TOLERANCE,
+            "{} disagrees with value iteration",
+            name
+        );
+    }
+
+    // LRTDP only backs up the states that matter from the start. No game can score more than
+    // the highest face on every remaining turn, so that is an admissible heuristic.
+    let other = LrtdpSolver::new(&mdp, 1.0, |state| (state.turns * DICE) as f64)
+        .solve()
+        .unwrap();
+    println!(
+        "LRTDP: {} trials, {} of {} states covered, initial value {:.6} (instead of {:.6})",
+        other.iterations(),
+        other.policy().actions().iter().flatten().count(),
+        mdp.states().len(),
+        other.values()[0],
+        solution.values()[0]
+    );
+    assert!(
+        (other.values()[0] - solution.values()[0]).abs() < TOLERANCE,
+        "LRTDP disagrees with value iteration"
+    );
+    policy.print(&mdp, solution.values());
 
     // println!("================ Take Threshold ================\n");
 }
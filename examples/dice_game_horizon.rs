@@ -0,0 +1,72 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    mdp::MdpBuilder,
+    model::SingleActionBuilder,
+    solver::{FiniteHorizonSolver, Solver},
+};
+
+const NUM_TURNS: usize = 100;
+const DICE: isize = 20;
+
+#[derive(Debug, Hash)]
+struct Roll;
+#[derive(Debug, Hash)]
+struct Take;
+
+// Same game as `dice_game`, but the number of turns left is handled by the solver
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct WorldState {
+    dice: isize,
+}
+
+fn main() {
+    let initial_state = WorldState { dice: -1 };
+
+    let mdp = MdpBuilder::new(initial_state.clone())
+        .add_action(Box::new({
+            let mut a = SingleActionBuilder::<WorldState, Roll>::new(Roll);
+            for v in 0..DICE {
+                a = a.outcome(Rc::new(move |state, _| {
+                    state.dice = v + 1;
+                    1.0 / DICE as f64
+                }));
+            }
+            a
+        }))
+        .add_action(Box::new(
+            SingleActionBuilder::<WorldState, Take>::new(Take)
+                .precondition(Rc::new(|state| state.dice >= 0))
+                .outcome(Rc::new(move |state, reward| {
+                    *reward = state.dice as f64;
+                    1.0
+                })),
+        ))
+        .build();
+
+    let mut solver = FiniteHorizonSolver::new(&mdp, 1.0, NUM_TURNS + 1);
+    let solution = solver.solve().unwrap();
+    println!(
+        "{} states, {} steps handled by the solver",
+        mdp.states().len(),
+        solver.horizon()
+    );
+    println!(
+        "Expected total: {:.1}\n",
+        solution.values()[mdp.index_of_state(&initial_state).unwrap()]
+    );
+
+    println!("================ Take Threshold ================\n");
+    for t in 0..solver.horizon() {
+        let policy = solver.policy(t);
+        let threshold = (0..mdp.states().len())
+            .filter(|&i| {
+                policy
+                    .get_action(i)
+                    .is_some_and(|a| a.to_string() == "Take")
+            })
+            .map(|i| mdp.states()[i].dice)
+            .min();
+        println!("Turn {:>3}: take from {:?}", t, threshold);
+    }
+}
@@ -0,0 +1,252 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    game::{solve_matrix_game, GameBuilder, Owner, ShapleySolver, SimultaneousActionBuilder},
+    model::{GrounableAction, GroundingActionBuilder},
+    solver::Solver,
+};
+
+// A penalty shootout between us (max) and them (min), three kicks each.
+// We win the coin toss and pick whether to kick first, then they pick a keeper to face our
+// kicks. On every kick both the kicker and the keeper pick a side at the same time. Kickers
+// are better at shooting left, and kick worse when their team is behind. Winning is worth 1
+// and losing -1.
+const KICKS: u8 = 3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Team {
+    Us,
+    Them,
+}
+use Team::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Keeper {
+    // Saves more of the kicks it guesses right
+    Tall,
+    // Gets to more of the kicks it guesses wrong
+    Quick,
+}
+use Keeper::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Side {
+    Left,
+    Right,
+}
+use Side::*;
+
+impl GrounableAction for Side {
+    fn enumerate() -> Vec<Self> {
+        vec![Left, Right]
+    }
+}
+
+#[derive(Hash, Debug)]
+struct KickFirst(Team);
+
+impl GrounableAction for KickFirst {
+    fn enumerate() -> Vec<Self> {
+        vec![KickFirst(Us), KickFirst(Them)]
+    }
+}
+
+#[derive(Hash, Debug)]
+struct PickKeeper(Keeper);
+
+impl GrounableAction for PickKeeper {
+    fn enumerate() -> Vec<Self> {
+        vec![PickKeeper(Tall), PickKeeper(Quick)]
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Shootout {
+    first: Option<Team>,
+    // Their keeper, ours is always the same
+    keeper: Option<Keeper>,
+    kicks: u8,
+    ours: u8,
+    theirs: u8,
+}
+
+impl Shootout {
+    fn kicker(&self) -> Team {
+        match (self.first, self.kicks % 2) {
+            (Some(team), 0) => team,
+            (Some(Us), _) => Them,
+            _ => Us,
+        }
+    }
+
+    // Over when all kicks are taken, or one team can't catch up anymore
+    fn is_over(&self) -> bool {
+        let taken = |team| (self.kicks + u8::from(self.first == Some(team))) / 2;
+        let (ours_left, theirs_left) = (KICKS - taken(Us), KICKS - taken(Them));
+        self.kicks == 2 * KICKS
+            || self.ours + ours_left < self.theirs
+            || self.theirs + theirs_left < self.ours
+    }
+
+    fn owner(&self) -> Owner {
+        match (self.first, self.keeper) {
+            (None, _) => Owner::Max,
+            (_, None) => Owner::Min,
+            _ => Owner::Simultaneous,
+        }
+    }
+
+    // The chance that the kicker scores, given where the kicker shoots and the keeper dives
+    fn scoring_probability(&self, shot: Side, dive: Side) -> f64 {
+        let mut probability = match (shot, shot == dive) {
+            (Left, false) => 0.9,
+            (Right, false) => 0.8,
+            (Left, true) => 0.4,
+            (Right, true) => 0.3,
+        };
+        if self.kicker() == Us {
+            probability -= match (self.keeper.unwrap(), shot == dive) {
+                (Tall, true) => 0.15,
+                (Quick, false) => 0.1,
+                _ => 0.0,
+            };
+        }
+        let (score, other) = match self.kicker() {
+            Us => (self.ours, self.theirs),
+            Them => (self.theirs, self.ours),
+        };
+        if score < other {
+            probability -= 0.1;
+        }
+        probability
+    }
+
+    fn kick(&mut self, reward: &mut f64, goal: bool) {
+        if goal {
+            match self.kicker() {
+                Us => self.ours += 1,
+                Them => self.theirs += 1,
+            }
+        }
+        self.kicks += 1;
+        if self.is_over() {
+            *reward = (self.ours as f64 - self.theirs as f64).signum();
+        }
+    }
+}
+
+fn main() {
+    // A warm-up: rock paper scissors has value 0 and a uniform strategy
+    let rock_paper_scissors = solve_matrix_game(&[
+        vec![0.0, -1.0, 1.0],
+        vec![1.0, 0.0, -1.0],
+        vec![-1.0, 1.0, 0.0],
+    ]);
+    println!(
+        "Rock paper scissors: value {:.3}, strategy {:.3?}",
+        rock_paper_scissors.value(),
+        rock_paper_scissors.max_strategy()
+    );
+
+    let initial_state = Shootout {
+        first: None,
+        keeper: None,
+        kicks: 0,
+        ours: 0,
+        theirs: 0,
+    };
+
+    // Both players pick a side; the max player kicks on our kicks and keeps on theirs
+    let shot = |state: &Shootout, ours: Side, theirs: Side| match state.kicker() {
+        Us => (ours, theirs),
+        Them => (theirs, ours),
+    };
+    let game = GameBuilder::new(initial_state, Rc::new(Shootout::owner))
+        .add_action(Box::new(
+            GroundingActionBuilder::<Shootout, KickFirst>::new()
+                .precondition(Rc::new(|_| Rc::new(|state| state.first.is_none())))
+                .outcome(Rc::new(|action| {
+                    Rc::new(move |state, _reward| {
+                        state.first = Some(action.0);
+                        1.0
+                    })
+                })),
+        ))
+        .add_action(Box::new(
+            GroundingActionBuilder::<Shootout, PickKeeper>::new()
+                .precondition(Rc::new(|_| {
+                    Rc::new(|state| state.first.is_some() && state.keeper.is_none())
+                }))
+                .outcome(Rc::new(|action| {
+                    Rc::new(move |state, _reward| {
+                        state.keeper = Some(action.0);
+                        1.0
+                    })
+                })),
+        ))
+        .add_simultaneous_action(
+            SimultaneousActionBuilder::<Shootout, Side, Side>::new()
+                .precondition(Rc::new(|state| !state.is_over()))
+                .outcome(Rc::new(move |ours, theirs| {
+                    Rc::new(move |state, reward| {
+                        let (kick, dive) = shot(state, *ours, *theirs);
+                        let probability = state.scoring_probability(kick, dive);
+                        state.kick(reward, true);
+                        probability
+                    })
+                }))
+                .outcome(Rc::new(move |ours, theirs| {
+                    Rc::new(move |state, reward| {
+                        let (kick, dive) = shot(state, *ours, *theirs);
+                        let probability = 1.0 - state.scoring_probability(kick, dive);
+                        state.kick(reward, false);
+                        probability
+                    })
+                })),
+        )
+        .build();
+
+    let mut solver = ShapleySolver::new(&game, 1.0);
+    let solution = solver.solve().unwrap();
+    println!(
+        "\n{} states, solved in {} iterations. We win by {:.3} on average",
+        game.mdp().states().len(),
+        solution.iterations(),
+        solution.values()[0]
+    );
+
+    let states = game.mdp().states();
+    println!("We pick {}", solution.policy().get_action(0).unwrap());
+    for (i, state) in states.iter().enumerate() {
+        if let (Some(first), None) = (state.first, state.keeper) {
+            println!(
+                "With {:?} kicking first, they pick {}, worth {:.3}",
+                first,
+                solution.policy().get_action(i).unwrap(),
+                solution.values()[i]
+            );
+        }
+    }
+
+    // The opening kick for every order and keeper
+    println!();
+    for (i, state) in states.iter().enumerate() {
+        if let (0, Some(keeper)) = (state.kicks, state.keeper) {
+            let strategy = |distribution: &[(_, f64)]| {
+                distribution
+                    .iter()
+                    .map(|(side, p)| format!("{} {:.2}", side, p))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            println!(
+                "{:?} kick against the {:?} keeper, worth {:.3}: we play {}, they play {}",
+                state.kicker(),
+                keeper,
+                solution.values()[i],
+                strategy(solver.max_strategy().distribution(i)),
+                strategy(solver.min_strategy().distribution(i))
+            );
+        }
+    }
+}
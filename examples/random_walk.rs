@@ -0,0 +1,104 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    learning::{LearningRate, Simulator, TdPrediction, Trace},
+    mdp::MdpBuilder,
+    model::SingleActionBuilder,
+    solver::{Solver, ValueIterationSolver},
+};
+
+// The 5 state random walk from Sutton and Barto (Example 6.2)
+// States A to E are 1 to 5, and the walk starts in the middle.
+const RIGHT_END: usize = 6;
+const RUNS: u64 = 100;
+const EPISODES: usize = 10;
+
+#[derive(Hash, PartialEq, Eq, Clone, Debug)]
+struct Position(usize);
+
+#[derive(Debug, Hash)]
+struct Step;
+
+fn main() {
+    let mdp = MdpBuilder::new(Position(3))
+        .add_action(Box::new(
+            SingleActionBuilder::<Position, Step>::new(Step)
+                .precondition(Rc::new(|pos| pos.0 != 0 && pos.0 != RIGHT_END))
+                .outcome(Rc::new(|pos, _reward| {
+                    pos.0 -= 1;
+                    0.5
+                }))
+                .outcome(Rc::new(|pos, reward| {
+                    pos.0 += 1;
+                    if pos.0 == RIGHT_END {
+                        *reward = 1.0;
+                    }
+                    0.5
+                })),
+        ))
+        .build();
+
+    // There is only one action, so this is the policy being evaluated. Its values are 1/6 to
+    // 5/6 from A to E.
+    let policy = ValueIterationSolver::new(&mdp, 1.0)
+        .solve()
+        .unwrap()
+        .policy()
+        .clone();
+
+    // Average RMS error over the first episodes, like figure 12.3. Replacing traces hold up
+    // better with large lambdas and step sizes, where accumulating traces start to blow up.
+    for trace in [Trace::Accumulating, Trace::Replacing] {
+        println!(
+            "{:?} traces: RMS error over the first {} episodes",
+            trace, EPISODES
+        );
+        for rate in [0.1, 0.2, 0.4] {
+            let errors = [0.0, 0.4, 0.8, 0.9, 0.95]
+                .iter()
+                .map(|&lambda| {
+                    let total = (0..RUNS)
+                        .map(|run| {
+                            let mut simulator = Simulator::new(&mdp).seed(run);
+                            let errors = TdPrediction::new(1.0)
+                                .lambda(lambda)
+                                .trace(trace)
+                                .learning_rate(LearningRate::Constant(rate))
+                                .initial_value(0.5)
+                                .seed(run)
+                                .rms_errors(&mdp, &mut simulator, &policy, EPISODES);
+                            errors.iter().sum::<f64>() / EPISODES as f64
+                        })
+                        .sum::<f64>();
+                    format!("λ {}: {:.3}", lambda, total / RUNS as f64)
+                })
+                .collect::<Vec<_>>();
+            println!("  α {}: {}", rate, errors.join(", "));
+        }
+        println!();
+    }
+
+    let mut simulator = Simulator::new(&mdp).seed(1);
+    let values = TdPrediction::new(1.0)
+        .learning_rate(LearningRate::Constant(0.01))
+        .initial_value(0.5)
+        .evaluate(&mut simulator, &policy, 10000);
+    // Terminal states are left out, and the states are numbered from the start.
+    let mut estimates = mdp
+        .states()
+        .iter()
+        .zip(values)
+        .filter(|(pos, _)| pos.0 != 0 && pos.0 != RIGHT_END)
+        .map(|(pos, value)| (pos.0, value))
+        .collect::<Vec<_>>();
+    estimates.sort_by_key(|&(pos, _)| pos);
+    println!("TD(0) after 10000 episodes:");
+    for (pos, value) in estimates {
+        println!(
+            "  {}: {:.3} (exact {:.3})",
+            (b'A' + pos as u8 - 1) as char,
+            value,
+            pos as f64 / 6.0
+        );
+    }
+}
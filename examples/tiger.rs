@@ -0,0 +1,171 @@
+use std::rc::Rc;
+
+use mdp_rs::{
+    pomdp::{
+        FibSolver, ObservingActionBuilder, PointBasedMethod, PointBasedSolver, Pomdp, PomdpBuilder,
+        QmdpSolver, ValueFunction,
+    },
+    rng::Rng,
+};
+
+// The tiger problem from Kaelbling, Littman and Cassandra (1998)
+// A tiger is behind one of two doors and treasure behind the other. Listening costs 1 and
+// hears the tiger on the correct side 85% of the time. Opening a door ends the round, and the
+// tiger is placed behind a random door again.
+const ACCURACY: f64 = 0.85;
+const DISCOUNT: f64 = 0.95;
+const RUNS: u64 = 1000;
+const STEPS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Side {
+    Left,
+    Right,
+}
+use Side::*;
+
+impl Side {
+    fn other(&self) -> Side {
+        match self {
+            Left => Right,
+            Right => Left,
+        }
+    }
+}
+
+// Where the tiger is
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Tiger(Side);
+
+// Which side the growl seemed to come from
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+struct Growl(Side);
+
+#[derive(Debug, Hash)]
+struct Listen;
+#[derive(Debug, Hash)]
+struct Open(Side);
+
+fn open(door: Side) -> ObservingActionBuilder<Tiger, Open, Growl> {
+    let mut builder = ObservingActionBuilder::<Tiger, Open, Growl>::new(Open(door));
+    for side in [Left, Right] {
+        builder = builder.outcome(Rc::new(move |tiger, reward, growls| {
+            *reward = if tiger.0 == door { -100.0 } else { 10.0 };
+            tiger.0 = side;
+            // Nothing to hear after the reset
+            growls.push((Growl(Left), 0.5));
+            growls.push((Growl(Right), 0.5));
+            0.5
+        }));
+    }
+    builder
+}
+
+fn main() {
+    let pomdp = PomdpBuilder::new(vec![(Tiger(Left), 0.5), (Tiger(Right), 0.5)])
+        .add_action(ObservingActionBuilder::new(Listen).outcome(Rc::new(
+            |tiger, reward, growls| {
+                *reward = -1.0;
+                growls.push((Growl(tiger.0), ACCURACY));
+                growls.push((Growl(tiger.0.other()), 1.0 - ACCURACY));
+                1.0
+            },
+        )))
+        .add_action(open(Left))
+        .add_action(open(Right))
+        .build();
+
+    let states = pomdp.mdp().states();
+    let actions = pomdp.actions(pomdp.initial_belief());
+    let listen = actions.iter().find(|a| a.to_string() == "Listen").unwrap();
+    let open_right = actions
+        .iter()
+        .find(|a| a.to_string() == "Open(Right)")
+        .unwrap();
+    let growl_left = pomdp.index_of_observation(&Growl(Left)).unwrap();
+    let growl_right = pomdp.index_of_observation(&Growl(Right)).unwrap();
+    let left = pomdp.mdp().index_of_state(&Tiger(Left)).unwrap();
+
+    // Hearing the tiger on the same side twice makes opening the other door worth it
+    let mut belief = pomdp.initial_belief().clone();
+    for (heard, growl) in [
+        ("left", growl_left),
+        ("left", growl_left),
+        ("right", growl_right),
+    ] {
+        println!(
+            "P(tiger left) = {:.3}, opening the right door is worth {:.1}, P(hearing {}) = {:.3}",
+            belief.probability(left),
+            pomdp.expected_reward(&belief, open_right),
+            heard,
+            pomdp.observation_likelihood(&belief, listen, growl)
+        );
+        belief = pomdp.update(&belief, listen, growl).unwrap();
+    }
+    println!(
+        "P(tiger left) = {:.3}, most likely {:?}",
+        belief.probability(left),
+        states[belief.most_likely()]
+    );
+
+    // Opening a door forgets everything
+    let belief = pomdp.update(&belief, open_right, growl_left).unwrap();
+    println!(
+        "After opening a door: P(tiger left) = {:.3}",
+        belief.probability(left)
+    );
+
+    // Plan over beliefs. QMDP and FIB are upper bounds on the value, and QMDP is far off since
+    // it assumes it will know where the tiger is after the next step. Here all of them end up
+    // with the same policy: listen until one side is heard twice more than the other.
+    let solvers = [
+        (
+            "PBVI",
+            PointBasedSolver::new(&pomdp, DISCOUNT)
+                .method(PointBasedMethod::Pbvi)
+                .solve(),
+        ),
+        (
+            "Perseus",
+            PointBasedSolver::new(&pomdp, DISCOUNT)
+                .method(PointBasedMethod::Perseus)
+                .solve(),
+        ),
+        ("QMDP", QmdpSolver::new(&pomdp, DISCOUNT).solve()),
+        ("FIB", FibSolver::new(&pomdp, DISCOUNT).solve()),
+    ];
+    println!();
+    for (name, value_function) in solvers {
+        let value_function = value_function.unwrap();
+        let initial = pomdp.initial_belief();
+        println!(
+            "{}: {} vectors after {} iterations, the initial belief is worth {:.2} and starts with {}, simulated return {:.2}",
+            name,
+            value_function.vectors().len(),
+            value_function.iterations(),
+            value_function.value(initial),
+            value_function.action(initial).unwrap(),
+            simulate(&pomdp, &value_function)
+        );
+    }
+}
+
+// Average discounted return of following the value function while tracking the belief
+fn simulate(pomdp: &Pomdp<Tiger, Growl>, value_function: &ValueFunction) -> f64 {
+    let mut rng = Rng::new(1);
+    let mut total = 0.0;
+    for _ in 0..RUNS {
+        let mut belief = pomdp.initial_belief().clone();
+        let mut state = belief.sample(&mut rng);
+        let mut discount = 1.0;
+        for _ in 0..STEPS {
+            let action = value_function.action(&belief).unwrap();
+            let (next, observation, reward) = pomdp.step(state, action, &mut rng);
+            total += discount * reward;
+            discount *= DISCOUNT;
+            belief = pomdp.update(&belief, action, observation).unwrap();
+            state = next;
+        }
+    }
+    total / RUNS as f64
+}
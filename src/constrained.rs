@@ -0,0 +1,276 @@
+use std::{collections::HashMap, marker::PhantomData, rc::Rc};
+
+use crate::{
+    mdp::{Mdp, MdpBuilder},
+    model::{
+        self, ActionBox, ActionType, CostingOutcomeFn, GrounableAction, IActionBuilder, State,
+    },
+    policy::StochasticPolicy,
+    solver::evaluate_stochastic_policy,
+};
+
+mod solver;
+
+pub use solver::{ConstrainedMethod, ConstrainedSolution, ConstrainedSolver};
+
+type PreconditionFn<S> = dyn Fn(&S) -> bool;
+type ActionBasedPreconditionFn<S, A> = dyn Fn(Rc<A>) -> Rc<PreconditionFn<S>>;
+type ActionBasedCostingOutcomeFn<S, A> = dyn Fn(Rc<A>) -> Rc<CostingOutcomeFn<S>>;
+
+/// An `Mdp` whose transitions also have named costs, like the risk of getting banned.
+///
+/// A constrained problem maximises the rewards while keeping the expected discounted total of
+/// some costs under a budget, see `ConstrainedSolver`.
+pub struct ConstrainedMdp<S: State> {
+    mdp: Mdp<S>,
+    cost_names: Vec<&'static str>,
+    // The costs of every transition, in the same order as `Mdp::actions`.
+    costs: Vec<HashMap<ActionBox, Vec<Vec<f64>>>>,
+}
+
+impl<S: State> ConstrainedMdp<S> {
+    /// Collects the costs the outcomes reported on the transitions of `mdp`. Every cost that
+    /// a transition doesn't report is 0 there.
+    pub fn new(mdp: Mdp<S>) -> Self {
+        let mut cost_names: Vec<&'static str> = vec![];
+        for i in 0..mdp.states().len() {
+            let mut actions = mdp.actions(i).iter().collect::<Vec<_>>();
+            actions.sort_by_key(|(action, _)| action.sort_key());
+            for (name, _) in actions
+                .into_iter()
+                .flat_map(|(_, ts)| ts)
+                .flat_map(|t| t.costs())
+            {
+                if !cost_names.contains(name) {
+                    cost_names.push(name);
+                }
+            }
+        }
+
+        let costs = (0..mdp.states().len())
+            .map(|i| {
+                mdp.actions(i)
+                    .iter()
+                    .map(|(action, transitions)| {
+                        let transitions = transitions
+                            .iter()
+                            .map(|t| {
+                                let mut costs = vec![0.0; cost_names.len()];
+                                for (name, amount) in t.costs() {
+                                    let k = cost_names.iter().position(|n| n == name).unwrap();
+                                    costs[k] += amount;
+                                }
+                                costs
+                            })
+                            .collect();
+                        (action.clone(), transitions)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            mdp,
+            cost_names,
+            costs,
+        }
+    }
+
+    pub fn mdp(&self) -> &Mdp<S> {
+        &self.mdp
+    }
+
+    /// Every cost that any outcome reports, in the order they first came up.
+    pub fn cost_names(&self) -> &[&'static str] {
+        self.cost_names.as_ref()
+    }
+
+    pub fn index_of_cost(&self, name: &str) -> Option<usize> {
+        self.cost_names.iter().position(|&n| n == name)
+    }
+
+    /// The costs of each transition of an action, lined up with `Mdp::actions` and indexed
+    /// like `cost_names`.
+    pub fn costs(&self, state: usize, action: &ActionBox) -> &[Vec<f64>] {
+        self.costs[state][action].as_ref()
+    }
+
+    /// The expected cost of taking an action once, for every cost.
+    pub fn expected_costs(&self, state: usize, action: &ActionBox) -> Vec<f64> {
+        let mut expected = vec![0.0; self.cost_names.len()];
+        for (t, costs) in self.mdp.actions(state)[action]
+            .iter()
+            .zip(self.costs(state, action))
+        {
+            for (total, cost) in expected.iter_mut().zip(costs) {
+                *total += t.probability() * cost;
+            }
+        }
+        expected
+    }
+
+    /// The expected discounted total of every cost under a policy, from every state.
+    pub fn cost_values(&self, policy: &StochasticPolicy, discount: f64) -> Vec<Vec<f64>> {
+        (0..self.cost_names.len())
+            .map(|k| {
+                let mut weights = vec![0.0; self.cost_names.len()];
+                weights[k] = 1.0;
+                evaluate_stochastic_policy(
+                    &self.scalarised(0.0, &weights),
+                    policy.distributions(),
+                    discount,
+                )
+            })
+            .collect()
+    }
+
+    // The same model, with every reward replaced by
+    // `reward_weight * reward + sum_k cost_weights[k] * cost_k`.
+    pub(crate) fn scalarised(&self, reward_weight: f64, cost_weights: &[f64]) -> Mdp<S> {
+        let actions_from_states = (0..self.mdp.states().len())
+            .map(|i| {
+                self.mdp
+                    .actions(i)
+                    .iter()
+                    .map(|(action, transitions)| {
+                        let transitions = transitions
+                            .iter()
+                            .zip(self.costs(i, action))
+                            .map(|(t, costs)| {
+                                let reward = reward_weight * t.reward()
+                                    + costs
+                                        .iter()
+                                        .zip(cost_weights)
+                                        .map(|(c, w)| c * w)
+                                        .sum::<f64>();
+                                t.with_reward(reward)
+                            })
+                            .collect();
+                        (action.clone(), transitions)
+                    })
+                    .collect()
+            })
+            .collect();
+        Mdp::from_transitions(self.mdp.states().to_vec(), actions_from_states)
+    }
+}
+
+/// A `SingleActionBuilder` whose outcomes also report costs, as `(name, amount)` pairs.
+/// Costs with the same name add up, and costs that aren't reported are 0.
+pub struct CostingActionBuilder<S: State, A: ActionType> {
+    preconditions: Vec<Rc<PreconditionFn<S>>>,
+    outcomes: Vec<Rc<CostingOutcomeFn<S>>>,
+    action: Rc<A>,
+}
+
+impl<S: State, A: ActionType + 'static> CostingActionBuilder<S, A> {
+    pub fn new(action: A) -> Self {
+        Self {
+            preconditions: vec![],
+            outcomes: vec![],
+            action: Rc::new(action),
+        }
+    }
+
+    pub fn precondition(mut self, valid: Rc<PreconditionFn<S>>) -> Self {
+        self.preconditions.push(valid);
+        self
+    }
+
+    pub fn outcome(mut self, effect: Rc<CostingOutcomeFn<S>>) -> Self {
+        self.outcomes.push(effect);
+        self
+    }
+
+    pub fn build(&self, action_index: usize) -> model::Action<S> {
+        model::Action::with_costs(
+            ActionBox::new(action_index, self.action.clone()),
+            self.preconditions.clone(),
+            self.outcomes.clone(),
+        )
+    }
+}
+
+impl<S: State, A: ActionType + 'static> IActionBuilder<S> for CostingActionBuilder<S, A> {
+    fn build(&self, action_index: usize) -> Vec<model::Action<S>> {
+        vec![self.build(action_index)]
+    }
+}
+
+/// A `GroundingActionBuilder` whose outcomes also report costs, like `CostingActionBuilder`.
+pub struct GroundingCostingActionBuilder<S: State, A: ActionType> {
+    preconditions: Vec<Rc<ActionBasedPreconditionFn<S, A>>>,
+    outcomes: Vec<Rc<ActionBasedCostingOutcomeFn<S, A>>>,
+    action_type: PhantomData<A>,
+}
+
+impl<S: State, A: ActionType + GrounableAction + 'static> Default
+    for GroundingCostingActionBuilder<S, A>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: State, A: ActionType + GrounableAction + 'static> GroundingCostingActionBuilder<S, A> {
+    pub fn new() -> Self {
+        Self {
+            preconditions: vec![],
+            outcomes: vec![],
+            action_type: PhantomData,
+        }
+    }
+
+    pub fn precondition(mut self, valid: Rc<ActionBasedPreconditionFn<S, A>>) -> Self {
+        self.preconditions.push(valid);
+        self
+    }
+
+    pub fn outcome(mut self, effect: Rc<ActionBasedCostingOutcomeFn<S, A>>) -> Self {
+        self.outcomes.push(effect);
+        self
+    }
+
+    pub fn build(&self, action_index: usize) -> Vec<model::Action<S>> {
+        A::enumerate()
+            .into_iter()
+            .map(|action| {
+                let a = Rc::new(action);
+                let preconditions = self.preconditions.iter().map(|p| p(a.clone())).collect();
+                let outcomes = self.outcomes.iter().map(|o| o(a.clone())).collect();
+                model::Action::with_costs(ActionBox::new(action_index, a), preconditions, outcomes)
+            })
+            .collect()
+    }
+}
+
+impl<S: State, A: ActionType + GrounableAction + 'static> IActionBuilder<S>
+    for GroundingCostingActionBuilder<S, A>
+{
+    fn build(&self, action_index: usize) -> Vec<model::Action<S>> {
+        GroundingCostingActionBuilder::build(self, action_index)
+    }
+}
+
+/// Builds a `ConstrainedMdp` like `MdpBuilder` builds an `Mdp`. Actions from the other
+/// builders have no costs.
+pub struct ConstrainedMdpBuilder<S: State> {
+    inner: MdpBuilder<S>,
+}
+
+impl<S: State> ConstrainedMdpBuilder<S> {
+    pub fn new(initial_state: S) -> Self {
+        Self {
+            inner: MdpBuilder::new(initial_state),
+        }
+    }
+
+    pub fn add_action(mut self, action_builder: Box<dyn IActionBuilder<S>>) -> Self {
+        self.inner = self.inner.add_action(action_builder);
+        self
+    }
+
+    pub fn build(self) -> ConstrainedMdp<S> {
+        ConstrainedMdp::new(self.inner.build())
+    }
+}
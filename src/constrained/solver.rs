@@ -0,0 +1,411 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    lp::{LinearProgram, LpError, Relation},
+    model::{ActionBox, State},
+    policy::StochasticPolicy,
+    solver::{evaluate_stochastic_policy, Solver, SolverConfig, SolverError, ValueIterationSolver},
+};
+
+use super::ConstrainedMdp;
+
+// Bisection steps for the probability of the mixed action.
+const MIX_STEPS: usize = 50;
+
+/// How `ConstrainedSolver` finds its policy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConstrainedMethod {
+    /// Solves the occupancy measure LP of `LpSolver` with one more constraint per budget.
+    /// Exact, and the optimal policy may need to randomize in a few states.
+    OccupancyLp,
+    /// Moves a price on every budgeted cost by subgradient steps (shrinking with the square
+    /// root of the round), and solves the `Mdp` with the costs subtracted from the rewards at
+    /// that price. Returns the best deterministic policy within budget it came across. With a
+    /// single budget, that policy is also mixed with the last one over budget, randomizing in
+    /// one state so the budget is met exactly, and the mixture is returned if it's better.
+    Lagrangian { step_size: f64, rounds: usize },
+}
+
+/// A policy for a constrained problem, with what it achieves.
+#[derive(Debug, Clone)]
+pub struct ConstrainedSolution {
+    policy: StochasticPolicy,
+    values: Vec<f64>,
+    cost_values: Vec<Vec<f64>>,
+    reward: f64,
+    costs: Vec<f64>,
+    multipliers: Vec<f64>,
+    iterations: usize,
+    elapsed: Duration,
+}
+
+impl ConstrainedSolution {
+    pub fn policy(&self) -> &StochasticPolicy {
+        &self.policy
+    }
+
+    /// The values of the policy in every state, like `Solution::values`.
+    pub fn values(&self) -> &[f64] {
+        self.values.as_ref()
+    }
+
+    /// The expected discounted total of a cost from every state, by the index of the cost.
+    pub fn cost_values(&self, cost: usize) -> &[f64] {
+        self.cost_values[cost].as_ref()
+    }
+
+    /// The expected discounted reward from the initial distribution.
+    pub fn reward(&self) -> f64 {
+        self.reward
+    }
+
+    /// The expected discounted total of every cost from the initial distribution.
+    pub fn costs(&self) -> &[f64] {
+        self.costs.as_ref()
+    }
+
+    /// How much reward one more unit of budget would buy for every cost, 0 for costs without
+    /// a budget or with budget to spare. The Lagrangian gives the prices of the round that
+    /// found the policy within budget.
+    pub fn multipliers(&self) -> &[f64] {
+        self.multipliers.as_ref()
+    }
+
+    /// Simplex pivots for the LP, rounds for the Lagrangian.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Maximises the expected discounted reward from an initial distribution, while keeping the
+/// expected discounted total of some costs within a budget.
+pub struct ConstrainedSolver<'a, S: State> {
+    cmdp: &'a ConstrainedMdp<S>,
+    discount: f64,
+    config: SolverConfig,
+    method: ConstrainedMethod,
+    budgets: Vec<Option<f64>>,
+    weights: Vec<f64>,
+}
+
+impl<'a, S: State> ConstrainedSolver<'a, S> {
+    pub fn new(cmdp: &'a ConstrainedMdp<S>, discount: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&discount),
+            "the constrained solver needs a discount in [0, 1)"
+        );
+        let mut weights = vec![0.0; cmdp.mdp().states().len()];
+        weights[0] = 1.0;
+        Self {
+            cmdp,
+            discount,
+            config: SolverConfig::default(),
+            method: ConstrainedMethod::OccupancyLp,
+            budgets: vec![None; cmdp.cost_names().len()],
+            weights,
+        }
+    }
+
+    /// Passed on to the `ValueIterationSolver` of every Lagrangian round.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn method(mut self, method: ConstrainedMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Limits the expected discounted total of a cost. Costs without a budget are only
+    /// reported. Panics if no outcome reports the cost.
+    pub fn budget(mut self, cost: &str, limit: f64) -> Self {
+        let k = self
+            .cmdp
+            .index_of_cost(cost)
+            .unwrap_or_else(|| panic!("no outcome reports a {} cost", cost));
+        self.budgets[k] = Some(limit);
+        self
+    }
+
+    /// Where the problem starts. Defaults to the initial state.
+    pub fn initial_distribution(mut self, weights: Vec<f64>) -> Self {
+        assert_eq!(weights.len(), self.cmdp.mdp().states().len());
+        self.weights = weights;
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<ConstrainedSolution, SolverError> {
+        match self.method {
+            ConstrainedMethod::OccupancyLp => self.solve_lp(),
+            ConstrainedMethod::Lagrangian { step_size, rounds } => {
+                self.solve_lagrangian(step_size, rounds)
+            }
+        }
+    }
+
+    fn value_from_start(&self, values: &[f64]) -> f64 {
+        values
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(v, w)| v * w)
+            .sum::<f64>()
+    }
+
+    fn solution(
+        &self,
+        policy: StochasticPolicy,
+        multipliers: Vec<f64>,
+        iterations: usize,
+        start: Instant,
+    ) -> ConstrainedSolution {
+        let values =
+            evaluate_stochastic_policy(self.cmdp.mdp(), policy.distributions(), self.discount);
+        let cost_values = self.cmdp.cost_values(&policy, self.discount);
+        ConstrainedSolution {
+            reward: self.value_from_start(&values),
+            costs: cost_values
+                .iter()
+                .map(|c| self.value_from_start(c))
+                .collect(),
+            policy,
+            values,
+            cost_values,
+            multipliers,
+            iterations,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    fn solve_lp(&self) -> Result<ConstrainedSolution, SolverError> {
+        let start = Instant::now();
+        let mdp = self.cmdp.mdp();
+        let n = mdp.states().len();
+
+        // Like `LpSolver`, with a zero reward self loop in states without actions.
+        let mut variables = vec![];
+        for state in 0..n {
+            let mut actions = mdp.actions(state).keys().collect::<Vec<_>>();
+            actions.sort_by_key(|a| a.sort_key());
+            if actions.is_empty() {
+                variables.push((state, None));
+            }
+            for action in actions {
+                variables.push((state, Some(action)));
+            }
+        }
+
+        let mut rewards = vec![0.0; variables.len()];
+        let mut costs = vec![vec![0.0; variables.len()]; self.budgets.len()];
+        let mut flow = vec![vec![0.0; variables.len()]; n];
+        for (column, &(state, action)) in variables.iter().enumerate() {
+            flow[state][column] += 1.0;
+            let Some(action) = action else {
+                flow[state][column] -= self.discount;
+                continue;
+            };
+            for t in mdp.actions(state)[action].iter() {
+                rewards[column] += t.probability() * t.reward();
+                flow[t.to()][column] -= self.discount * t.probability();
+            }
+            for (k, cost) in self
+                .cmdp
+                .expected_costs(state, action)
+                .into_iter()
+                .enumerate()
+            {
+                costs[k][column] = cost;
+            }
+        }
+
+        let mut lp = flow
+            .into_iter()
+            .zip(self.weights.iter())
+            .fold(LinearProgram::maximize(rewards), |lp, (row, &weight)| {
+                lp.constraint(row, Relation::Equal, weight)
+            });
+        let budgeted = self
+            .budgets
+            .iter()
+            .enumerate()
+            .filter_map(|(k, budget)| budget.map(|b| (k, b)))
+            .collect::<Vec<_>>();
+        for &(k, budget) in budgeted.iter() {
+            lp = lp.constraint(costs[k].clone(), Relation::LessEqual, budget);
+        }
+        let lp_solution = match lp.solve() {
+            Ok(solution) => solution,
+            Err(LpError::Unbounded) => return Err(SolverError::Diverged { iterations: 0 }),
+            Err(LpError::Infeasible) => return Err(SolverError::Infeasible),
+        };
+
+        // The policy takes every action in proportion to how often it is taken. States the
+        // policy never reaches pick uniformly.
+        let mut distributions = StochasticPolicy::uniform(mdp).distributions().to_vec();
+        let occupancy = variables
+            .iter()
+            .zip(lp_solution.variables())
+            .filter_map(|(&(state, action), &x)| action.map(|a| (state, a, x)))
+            .collect::<Vec<_>>();
+        for (state, distribution) in distributions.iter_mut().enumerate() {
+            let total = occupancy
+                .iter()
+                .filter(|(s, _, _)| *s == state)
+                .map(|(_, _, x)| x)
+                .sum::<f64>();
+            if total > 1e-12 {
+                *distribution = occupancy
+                    .iter()
+                    .filter(|(s, _, x)| *s == state && *x > 1e-12 * total)
+                    .map(|(_, a, x)| ((*a).clone(), x / total))
+                    .collect();
+            }
+        }
+
+        let mut multipliers = vec![0.0; self.budgets.len()];
+        for (i, &(k, _)) in budgeted.iter().enumerate() {
+            multipliers[k] = lp_solution.duals()[n + i];
+        }
+        Ok(self.solution(
+            StochasticPolicy::new(distributions),
+            multipliers,
+            lp_solution.pivots(),
+            start,
+        ))
+    }
+
+    fn solve_lagrangian(
+        &self,
+        step_size: f64,
+        rounds: usize,
+    ) -> Result<ConstrainedSolution, SolverError> {
+        let start = Instant::now();
+        let mut multipliers = vec![0.0; self.budgets.len()];
+        let mut best: Option<ConstrainedSolution> = None;
+        let mut over_budget: Option<StochasticPolicy> = None;
+        let mut iterations = 0;
+        for round in 0..rounds {
+            iterations += 1;
+            let prices = multipliers.iter().map(|m: &f64| -m).collect::<Vec<_>>();
+            let mdp = self.cmdp.scalarised(1.0, &prices);
+            let policy = ValueIterationSolver::new(&mdp, self.discount)
+                .with_config(self.config.clone())
+                .solve()?
+                .policy()
+                .clone();
+            let solution = self.solution(
+                StochasticPolicy::from(&policy),
+                multipliers.clone(),
+                iterations,
+                start,
+            );
+
+            let excess = solution
+                .costs()
+                .iter()
+                .zip(self.budgets.iter())
+                .map(|(cost, budget)| budget.map_or(0.0, |b| cost - b))
+                .collect::<Vec<_>>();
+            let tolerance = self.config.tolerance(1.0);
+            let feasible = excess.iter().all(|&e| e <= tolerance);
+            // Complementary slackness: nothing to gain from spending more of the budget.
+            let optimal = feasible
+                && excess
+                    .iter()
+                    .zip(multipliers.iter())
+                    .all(|(e, m)| *m == 0.0 || e.abs() <= tolerance);
+            // Ties go to the later round, whose prices are closer to the optimal ones.
+            if !feasible {
+                over_budget = Some(solution.policy().clone());
+            } else if best
+                .as_ref()
+                .is_none_or(|b| solution.reward() >= b.reward())
+            {
+                best = Some(solution);
+            }
+            if optimal {
+                break;
+            }
+
+            let step = step_size / ((round + 1) as f64).sqrt();
+            for (m, e) in multipliers.iter_mut().zip(excess) {
+                *m = (*m + step * e).max(0.0);
+            }
+        }
+
+        let mut best = best.ok_or(SolverError::Infeasible)?;
+        let budgeted = self
+            .budgets
+            .iter()
+            .enumerate()
+            .filter_map(|(k, budget)| budget.map(|b| (k, b)))
+            .collect::<Vec<_>>();
+        if let ([(k, budget)], Some(over_budget)) = (budgeted.as_slice(), over_budget) {
+            let mixed = self.mix(best.policy(), &over_budget, *k, *budget);
+            let mixed = self.solution(mixed, best.multipliers.clone(), iterations, start);
+            if mixed.reward() > best.reward() {
+                best = mixed;
+            }
+        }
+        best.iterations = iterations;
+        best.elapsed = start.elapsed();
+        Ok(best)
+    }
+
+    // Switches `within` over to `over` one state at a time, until the next switch would break
+    // the budget of cost `k`. That state takes both actions instead, with the probability of
+    // the new one set to spend as much of the budget as possible.
+    fn mix(
+        &self,
+        within: &StochasticPolicy,
+        over: &StochasticPolicy,
+        k: usize,
+        budget: f64,
+    ) -> StochasticPolicy {
+        let mut weights = vec![0.0; self.budgets.len()];
+        weights[k] = 1.0;
+        let costs = self.cmdp.scalarised(0.0, &weights);
+        let cost = |distributions: &[Vec<(ActionBox, f64)>]| {
+            self.value_from_start(&evaluate_stochastic_policy(
+                &costs,
+                distributions,
+                self.discount,
+            ))
+        };
+        let mixed = |a: &[(ActionBox, f64)], b: &[(ActionBox, f64)], p: f64| {
+            a.iter()
+                .map(|(action, q)| (action.clone(), q * (1.0 - p)))
+                .chain(b.iter().map(|(action, q)| (action.clone(), q * p)))
+                .filter(|(_, q)| *q > 0.0)
+                .collect::<Vec<_>>()
+        };
+
+        let mut current = within.distributions().to_vec();
+        for (state, target) in over.distributions().iter().enumerate() {
+            if current[state] == *target {
+                continue;
+            }
+            let previous = std::mem::replace(&mut current[state], target.clone());
+            if cost(&current) <= budget {
+                continue;
+            }
+            let (mut low, mut high) = (0.0, 1.0);
+            for _ in 0..MIX_STEPS {
+                let p = (low + high) / 2.0;
+                current[state] = mixed(&previous, target, p);
+                if cost(&current) <= budget {
+                    low = p;
+                } else {
+                    high = p;
+                }
+            }
+            current[state] = mixed(&previous, target, low);
+            break;
+        }
+        StochasticPolicy::new(current)
+    }
+}
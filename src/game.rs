@@ -0,0 +1,360 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash, rc::Rc};
+
+use crate::{
+    lp::{LinearProgram, Relation},
+    mdp::Mdp,
+    model::{self, ActionBox, ActionType, GrounableAction, IActionBuilder, State},
+};
+
+mod shapley;
+
+pub use shapley::ShapleySolver;
+
+type PreconditionFn<S> = dyn Fn(&S) -> bool;
+type JointOutcomeFn<S, A, B> = dyn Fn(Rc<A>, Rc<B>) -> Rc<dyn Fn(&mut S, &mut f64) -> f64>;
+
+/// Who picks the action in a state of a `StochasticGame`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Owner {
+    /// The player that maximises the rewards.
+    Max,
+    /// The player that minimises them.
+    Min,
+    /// Both players pick a move at the same time, without seeing the other's.
+    Simultaneous,
+}
+
+/// The value of a zero-sum matrix game and optimal mixed strategies for both players.
+#[derive(Debug, Clone)]
+pub struct MatrixGameSolution {
+    value: f64,
+    max_strategy: Vec<f64>,
+    min_strategy: Vec<f64>,
+}
+
+impl MatrixGameSolution {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The probability of each row.
+    pub fn max_strategy(&self) -> &[f64] {
+        self.max_strategy.as_ref()
+    }
+
+    /// The probability of each column.
+    pub fn min_strategy(&self) -> &[f64] {
+        self.min_strategy.as_ref()
+    }
+}
+
+/// Solves a zero-sum matrix game where the max player picks a row, the min player a column,
+/// and the max player gets `payoffs[row][column]`.
+///
+/// Each player's strategy comes from its own LP: the max player maximises `v` subject to
+/// `sum_i x_i * payoffs[i][j] >= v` for every column, and the min player the other way
+/// around. The payoffs are shifted to be positive first, since LP variables can't be negative.
+pub fn solve_matrix_game(payoffs: &[Vec<f64>]) -> MatrixGameSolution {
+    let rows = payoffs.len();
+    let columns = payoffs[0].len();
+    let lowest = payoffs
+        .iter()
+        .flatten()
+        .copied()
+        .fold(f64::INFINITY, f64::min);
+    let shift = 1.0 - lowest;
+
+    // Variables: the strategy, then the value.
+    let mut max_lp = LinearProgram::maximize([vec![0.0; rows], vec![1.0]].concat());
+    for j in 0..columns {
+        let mut coefficients = payoffs.iter().map(|row| row[j] + shift).collect::<Vec<_>>();
+        coefficients.push(-1.0);
+        max_lp = max_lp.constraint(coefficients, Relation::GreaterEqual, 0.0);
+    }
+    max_lp = max_lp.constraint([vec![1.0; rows], vec![0.0]].concat(), Relation::Equal, 1.0);
+
+    let mut min_lp = LinearProgram::minimize([vec![0.0; columns], vec![1.0]].concat());
+    for row in payoffs.iter() {
+        let mut coefficients = row.iter().map(|p| p + shift).collect::<Vec<_>>();
+        coefficients.push(-1.0);
+        min_lp = min_lp.constraint(coefficients, Relation::LessEqual, 0.0);
+    }
+    min_lp = min_lp.constraint(
+        [vec![1.0; columns], vec![0.0]].concat(),
+        Relation::Equal,
+        1.0,
+    );
+
+    let max_solution = max_lp.solve().expect("matrix games always have a solution");
+    let min_solution = min_lp.solve().expect("matrix games always have a solution");
+    MatrixGameSolution {
+        value: max_solution.objective() - shift,
+        max_strategy: max_solution.variables()[..rows].to_vec(),
+        min_strategy: min_solution.variables()[..columns].to_vec(),
+    }
+}
+
+// The moves of both players in a state. Turn-based states only have moves for their owner,
+// and simultaneous ones also have the joint action of every pair of moves.
+#[derive(Debug, Clone, Default)]
+struct Moves {
+    max: Vec<ActionBox>,
+    min: Vec<ActionBox>,
+    joint: Vec<Vec<ActionBox>>,
+}
+
+/// A two player zero-sum stochastic game on top of an `Mdp`.
+///
+/// Every state is owned by the max player, the min player or both at once (`Owner`). The
+/// rewards go to the max player and are paid by the min player. In turn-based states the
+/// actions of the `Mdp` are the owner's moves, and in simultaneous states they are joint
+/// moves that are laid out as a matrix game.
+pub struct StochasticGame<S: State> {
+    mdp: Mdp<S>,
+    owners: Vec<Owner>,
+    moves: Vec<Moves>,
+}
+
+impl<S: State> StochasticGame<S> {
+    /// The underlying model, with every move (or pair of moves) as an action.
+    pub fn mdp(&self) -> &Mdp<S> {
+        &self.mdp
+    }
+
+    pub fn owner(&self, state: usize) -> Owner {
+        self.owners[state]
+    }
+
+    /// The moves of the max player in a state, in a stable order. Empty if it doesn't move.
+    pub fn max_moves(&self, state: usize) -> &[ActionBox] {
+        self.moves[state].max.as_ref()
+    }
+
+    /// The moves of the min player in a state, in a stable order. Empty if it doesn't move.
+    pub fn min_moves(&self, state: usize) -> &[ActionBox] {
+        self.moves[state].min.as_ref()
+    }
+
+    /// The action of the `Mdp` for a pair of moves in a simultaneous state, by their index in
+    /// `max_moves` and `min_moves`.
+    pub fn joint_action(&self, state: usize, max_move: usize, min_move: usize) -> &ActionBox {
+        &self.moves[state].joint[max_move][min_move]
+    }
+}
+
+#[derive(Debug, Hash)]
+struct Joint<A, B>(Rc<A>, Rc<B>);
+
+/// Like `GroundingActionBuilder`, but for a pair of moves picked at the same time in
+/// `Owner::Simultaneous` states: `A` enumerates the moves of the max player and `B` those of
+/// the min player. An outcome is given for every pair of moves.
+pub struct SimultaneousActionBuilder<S: State, A, B> {
+    preconditions: Vec<Rc<PreconditionFn<S>>>,
+    outcomes: Vec<Rc<JointOutcomeFn<S, A, B>>>,
+}
+
+impl<S: State, A, B> Default for SimultaneousActionBuilder<S, A, B>
+where
+    A: ActionType + GrounableAction + Debug + Hash + 'static,
+    B: ActionType + GrounableAction + Debug + Hash + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: State, A, B> SimultaneousActionBuilder<S, A, B>
+where
+    A: ActionType + GrounableAction + Debug + Hash + 'static,
+    B: ActionType + GrounableAction + Debug + Hash + 'static,
+{
+    pub fn new() -> Self {
+        Self {
+            preconditions: vec![],
+            outcomes: vec![],
+        }
+    }
+
+    /// Preconditions only depend on the state, so both players always have all their moves.
+    pub fn precondition(mut self, valid: Rc<PreconditionFn<S>>) -> Self {
+        self.preconditions.push(valid);
+        self
+    }
+
+    pub fn outcome(mut self, effect: Rc<JointOutcomeFn<S, A, B>>) -> Self {
+        self.outcomes.push(effect);
+        self
+    }
+}
+
+// Grounds a `SimultaneousActionBuilder` without its move types.
+trait JointActions<S: State> {
+    // The moves of the max and min player, and the grounded pairs of moves by row.
+    fn build(&self, action_index: usize) -> (Moves, Vec<model::Action<S>>);
+}
+
+impl<S: State, A, B> JointActions<S> for SimultaneousActionBuilder<S, A, B>
+where
+    A: ActionType + GrounableAction + Debug + Hash + 'static,
+    B: ActionType + GrounableAction + Debug + Hash + 'static,
+{
+    fn build(&self, action_index: usize) -> (Moves, Vec<model::Action<S>>) {
+        let max = A::enumerate().into_iter().map(Rc::new).collect::<Vec<_>>();
+        let min = B::enumerate().into_iter().map(Rc::new).collect::<Vec<_>>();
+        let mut moves = Moves {
+            max: max
+                .iter()
+                .map(|a| ActionBox::new(action_index, a.clone()))
+                .collect(),
+            min: min
+                .iter()
+                .map(|b| ActionBox::new(action_index, b.clone()))
+                .collect(),
+            joint: vec![],
+        };
+
+        let mut actions = vec![];
+        for a in max.iter() {
+            let mut row = vec![];
+            for b in min.iter() {
+                let joint = ActionBox::new(action_index, Rc::new(Joint(a.clone(), b.clone())));
+                let outcomes = self
+                    .outcomes
+                    .iter()
+                    .map(|outcome| outcome(a.clone(), b.clone()))
+                    .collect();
+                actions.push(model::Action::new(
+                    joint.clone(),
+                    self.preconditions.clone(),
+                    outcomes,
+                ));
+                row.push(joint);
+            }
+            moves.joint.push(row);
+        }
+        (moves, actions)
+    }
+}
+
+enum GameAction<S: State> {
+    TurnBased(Box<dyn IActionBuilder<S>>),
+    Simultaneous(Box<dyn JointActions<S>>),
+}
+
+/// Builds a `StochasticGame` like `MdpBuilder` builds an `Mdp`.
+///
+/// The owner of every state comes from a function of the state. Turn-based actions are added
+/// with `add_action` and are only available in `Owner::Max` and `Owner::Min` states, where they
+/// are the owner's moves. Simultaneous actions are only available in `Owner::Simultaneous`
+/// states, and only one of them may be available in a state.
+pub struct GameBuilder<S: State> {
+    initial_state: S,
+    owner: Rc<dyn Fn(&S) -> Owner>,
+    actions: Vec<GameAction<S>>,
+}
+
+impl<S: State + 'static> GameBuilder<S> {
+    pub fn new(initial_state: S, owner: Rc<dyn Fn(&S) -> Owner>) -> Self {
+        Self {
+            initial_state,
+            owner,
+            actions: vec![],
+        }
+    }
+
+    pub fn add_action(mut self, action_builder: Box<dyn IActionBuilder<S>>) -> Self {
+        self.actions.push(GameAction::TurnBased(action_builder));
+        self
+    }
+
+    pub fn add_simultaneous_action<A, B>(
+        mut self,
+        action_builder: SimultaneousActionBuilder<S, A, B>,
+    ) -> Self
+    where
+        A: ActionType + GrounableAction + Debug + Hash + 'static,
+        B: ActionType + GrounableAction + Debug + Hash + 'static,
+    {
+        self.actions
+            .push(GameAction::Simultaneous(Box::new(action_builder)));
+        self
+    }
+
+    /// Panics if more than one simultaneous action is available in a state.
+    pub fn build(self) -> StochasticGame<S> {
+        let mut actions = vec![];
+        // Which simultaneous action every joint action belongs to, and its row and column.
+        let mut joints = HashMap::new();
+        let mut matrices = vec![];
+        for (i, action) in self.actions.iter().enumerate() {
+            match action {
+                GameAction::TurnBased(builder) => {
+                    let owner = self.owner.clone();
+                    let turn_based: Rc<PreconditionFn<S>> =
+                        Rc::new(move |state| owner(state) != Owner::Simultaneous);
+                    actions.extend(
+                        builder
+                            .build(i)
+                            .into_iter()
+                            .map(|a| a.with_precondition(turn_based.clone())),
+                    );
+                }
+                GameAction::Simultaneous(builder) => {
+                    let owner = self.owner.clone();
+                    let simultaneous: Rc<PreconditionFn<S>> =
+                        Rc::new(move |state| owner(state) == Owner::Simultaneous);
+                    let (moves, joint_actions) = builder.build(i);
+                    for (row, joint) in moves.joint.iter().enumerate() {
+                        for (column, action) in joint.iter().enumerate() {
+                            joints.insert(action.clone(), (matrices.len(), row, column));
+                        }
+                    }
+                    matrices.push(moves);
+                    actions.extend(
+                        joint_actions
+                            .into_iter()
+                            .map(|a| a.with_precondition(simultaneous.clone())),
+                    );
+                }
+            }
+        }
+
+        let mdp = Mdp::new(self.initial_state, actions);
+        let owners = mdp
+            .states()
+            .iter()
+            .map(|s| (self.owner)(s))
+            .collect::<Vec<_>>();
+        let moves = (0..mdp.states().len())
+            .map(|i| {
+                let mut available = mdp.actions(i).keys().cloned().collect::<Vec<_>>();
+                available.sort_by_key(|a| a.sort_key());
+                match owners[i] {
+                    Owner::Max => Moves {
+                        max: available,
+                        ..Default::default()
+                    },
+                    Owner::Min => Moves {
+                        min: available,
+                        ..Default::default()
+                    },
+                    Owner::Simultaneous => {
+                        let mut matrix = None;
+                        for action in available.iter() {
+                            let (k, _, _) = joints[action];
+                            assert!(
+                                matrix.is_none_or(|m| m == k),
+                                "more than one simultaneous action is available in state {}",
+                                i
+                            );
+                            matrix = Some(k);
+                        }
+                        matrix.map(|k| matrices[k].clone()).unwrap_or_default()
+                    }
+                }
+            })
+            .collect();
+
+        StochasticGame { mdp, owners, moves }
+    }
+}
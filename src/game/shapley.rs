@@ -0,0 +1,193 @@
+use std::time::Instant;
+
+use crate::{
+    model::{ActionBox, State},
+    policy::{Policy, StochasticPolicy},
+    solver::{expected_value, Solution, Solver, SolverConfig, SolverError},
+};
+
+use super::{solve_matrix_game, Owner, StochasticGame};
+
+/// Shapley's value iteration for zero-sum stochastic games.
+///
+/// Like `ValueIterationSolver`, but every backup is the value of the owner's best move: the
+/// highest Q-value in `Owner::Max` states, the lowest in `Owner::Min` states, and the value of
+/// the matrix game of Q-values in `Owner::Simultaneous` states. With a discount below 1 this is
+/// a contraction, so it converges to the minimax values whatever the players do.
+///
+/// The `Solution` holds the values and, in simultaneous states, the joint action of the most
+/// likely move of each player. The mixed strategies are in `max_strategy` and `min_strategy`.
+pub struct ShapleySolver<'a, S: State> {
+    game: &'a StochasticGame<S>,
+    discount: f64,
+    config: SolverConfig,
+    max_strategy: StochasticPolicy,
+    min_strategy: StochasticPolicy,
+}
+
+impl<'a, S: State> ShapleySolver<'a, S> {
+    pub fn new(game: &'a StochasticGame<S>, discount: f64) -> Self {
+        let n = game.mdp().states().len();
+        Self {
+            game,
+            discount,
+            config: SolverConfig::default(),
+            max_strategy: StochasticPolicy::new(vec![vec![]; n]),
+            min_strategy: StochasticPolicy::new(vec![vec![]; n]),
+        }
+    }
+
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The strategy of the max player after the last `solve`, over its moves in
+    /// `StochasticGame::max_moves`. Empty in states where it doesn't move.
+    pub fn max_strategy(&self) -> &StochasticPolicy {
+        &self.max_strategy
+    }
+
+    /// The strategy of the min player after the last `solve`, over its moves in
+    /// `StochasticGame::min_moves`.
+    pub fn min_strategy(&self) -> &StochasticPolicy {
+        &self.min_strategy
+    }
+
+    fn q_value(&self, state: usize, action: &ActionBox, values: &[f64]) -> f64 {
+        expected_value(
+            &self.game.mdp().actions(state)[action],
+            values,
+            self.discount,
+        )
+    }
+
+    fn payoffs(&self, state: usize, values: &[f64]) -> Vec<Vec<f64>> {
+        (0..self.game.max_moves(state).len())
+            .map(|i| {
+                (0..self.game.min_moves(state).len())
+                    .map(|j| self.q_value(state, self.game.joint_action(state, i, j), values))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn backup(&self, state: usize, values: &[f64]) -> f64 {
+        let q_values = |moves: &[ActionBox]| {
+            moves
+                .iter()
+                .map(|a| self.q_value(state, a, values))
+                .collect::<Vec<_>>()
+        };
+        match self.game.owner(state) {
+            _ if self.game.mdp().actions(state).is_empty() => 0.0,
+            Owner::Max => q_values(self.game.max_moves(state))
+                .into_iter()
+                .fold(f64::NEG_INFINITY, f64::max),
+            Owner::Min => q_values(self.game.min_moves(state))
+                .into_iter()
+                .fold(f64::INFINITY, f64::min),
+            Owner::Simultaneous => solve_matrix_game(&self.payoffs(state, values)).value(),
+        }
+    }
+
+    // Reads the strategies off the values, and returns the residual.
+    fn solution(&mut self, values: &[f64], iterations: usize, start: Instant) -> Solution {
+        let game = self.game;
+        let n = game.mdp().states().len();
+        let mut actions = vec![None; n];
+        let mut max_strategy = vec![vec![]; n];
+        let mut min_strategy = vec![vec![]; n];
+        let mut residual: f64 = 0.0;
+        for state in 0..n {
+            if game.mdp().actions(state).is_empty() {
+                continue;
+            }
+            let best = |moves: &[ActionBox], sign: f64| {
+                moves
+                    .iter()
+                    .map(|a| (sign * self.q_value(state, a, values), a))
+                    .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                    .map(|(q, a)| (sign * q, a.clone()))
+                    .unwrap()
+            };
+            let value = match game.owner(state) {
+                Owner::Max => {
+                    let (q, action) = best(game.max_moves(state), 1.0);
+                    max_strategy[state] = vec![(action.clone(), 1.0)];
+                    actions[state] = Some(action);
+                    q
+                }
+                Owner::Min => {
+                    let (q, action) = best(game.min_moves(state), -1.0);
+                    min_strategy[state] = vec![(action.clone(), 1.0)];
+                    actions[state] = Some(action);
+                    q
+                }
+                Owner::Simultaneous => {
+                    let matrix_game = solve_matrix_game(&self.payoffs(state, values));
+                    let most_likely = |strategy: &[f64]| {
+                        strategy
+                            .iter()
+                            .enumerate()
+                            .map(|(i, p)| (*p, i))
+                            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                            .unwrap()
+                            .1
+                    };
+                    let (i, j) = (
+                        most_likely(matrix_game.max_strategy()),
+                        most_likely(matrix_game.min_strategy()),
+                    );
+                    actions[state] = Some(game.joint_action(state, i, j).clone());
+                    max_strategy[state] = mixed(game.max_moves(state), matrix_game.max_strategy());
+                    min_strategy[state] = mixed(game.min_moves(state), matrix_game.min_strategy());
+                    matrix_game.value()
+                }
+            };
+            residual = residual.max((value - values[state]).abs());
+        }
+        self.max_strategy = StochasticPolicy::new(max_strategy);
+        self.min_strategy = StochasticPolicy::new(min_strategy);
+        Solution::new(
+            values.to_vec(),
+            Policy::new(actions),
+            iterations,
+            residual,
+            start.elapsed(),
+        )
+    }
+}
+
+// Drops the moves that are never played.
+fn mixed(moves: &[ActionBox], strategy: &[f64]) -> Vec<(ActionBox, f64)> {
+    moves
+        .iter()
+        .zip(strategy)
+        .filter(|(_, &p)| p > 0.0)
+        .map(|(a, &p)| (a.clone(), p))
+        .collect()
+}
+
+impl<'a, S: State> Solver<S> for ShapleySolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let n = self.game.mdp().states().len();
+        let mut values = vec![0.0; n];
+        let mut iterations = 0;
+        loop {
+            let old_values = values;
+            values = (0..n).map(|i| self.backup(i, &old_values)).collect();
+            iterations += 1;
+            if self.config.converged(&old_values, &values) {
+                return Ok(self.solution(&values, iterations, start));
+            }
+            if self.config.diverged(&old_values, &values, self.discount) {
+                return Err(SolverError::Diverged { iterations });
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                return Err(error(self.solution(&values, iterations, start)));
+            }
+        }
+    }
+}
@@ -0,0 +1,64 @@
+/// Splits a directed graph, given as successor lists, into strongly connected components.
+///
+/// Components come out in reverse topological order: every edge leaving a component points
+/// into a component that was returned before it.
+pub fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    // Iterative version of Tarjan's algorithm, so long chains don't overflow the stack.
+    let n = successors.len();
+    let mut index = vec![usize::MAX; n];
+    let mut low_link = vec![0; n];
+    let mut on_stack = vec![false; n];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+
+    for root in 0..n {
+        if index[root] != usize::MAX {
+            continue;
+        }
+
+        let mut call_stack = vec![(root, 0)];
+        index[root] = next_index;
+        low_link[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+
+        while let Some((node, edge)) = call_stack.last_mut() {
+            let node = *node;
+            if let Some(&next) = successors[node].get(*edge) {
+                *edge += 1;
+                if index[next] == usize::MAX {
+                    index[next] = next_index;
+                    low_link[next] = next_index;
+                    next_index += 1;
+                    stack.push(next);
+                    on_stack[next] = true;
+                    call_stack.push((next, 0));
+                } else if on_stack[next] {
+                    low_link[node] = low_link[node].min(index[next]);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                low_link[parent] = low_link[parent].min(low_link[node]);
+            }
+
+            if low_link[node] == index[node] {
+                let mut component = vec![];
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+
+    components
+}
@@ -0,0 +1,388 @@
+use crate::{
+    mdp::Mdp,
+    model::{ActionBox, State},
+    policy::{Policy, StochasticPolicy},
+    rng::Rng,
+};
+
+mod double_q_learning;
+mod dyna_q;
+mod monte_carlo;
+mod q_learning;
+mod sarsa;
+mod td;
+
+pub use double_q_learning::DoubleQLearningAgent;
+pub use dyna_q::DynaQAgent;
+pub use monte_carlo::{ImportanceSampling, MonteCarloControlAgent, MonteCarloPrediction, Visits};
+pub use q_learning::QLearningAgent;
+pub use sarsa::{ExpectedSarsaAgent, SarsaAgent};
+pub use td::{TdPrediction, Trace};
+
+// Index of the action `policy` picks in the simulator's current state, if it has one.
+pub(crate) fn policy_action(simulator: &Simulator, policy: &Policy) -> Option<usize> {
+    let action = policy.get_action(simulator.state())?;
+    simulator.actions().iter().position(|a| a == action)
+}
+
+// Starts an episode in the initial state, or with exploring starts in a random state that
+// isn't terminal.
+pub(crate) fn start(simulator: &mut Simulator, exploring_starts: bool, rng: &mut Rng) {
+    if !exploring_starts {
+        simulator.reset();
+        return;
+    }
+    loop {
+        simulator.reset_to(rng.below(simulator.num_states()));
+        if !simulator.is_terminal() {
+            return;
+        }
+    }
+}
+
+/// What the agent gets to see after taking an action.
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    reward: f64,
+    state: usize,
+    terminal: bool,
+}
+
+impl Step {
+    pub fn reward(&self) -> f64 {
+        self.reward
+    }
+
+    /// Index of the next state.
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    /// Whether the next state has no actions, which ends the episode.
+    pub fn terminal(&self) -> bool {
+        self.terminal
+    }
+}
+
+/// Samples experience from an `Mdp`.
+///
+/// Outcomes are drawn with their transition probabilities, but the agent only ever sees the
+/// reward and the index of the next state, never the probabilities. Actions are referred to
+/// by their index in `actions()`, which is stable for a given state.
+pub struct Simulator {
+    actions: Vec<Vec<ActionBox>>,
+    // (next state, probability, reward) of every outcome, per state and action.
+    outcomes: Vec<Vec<Vec<(usize, f64, f64)>>>,
+    state: usize,
+    rng: Rng,
+}
+
+impl Simulator {
+    pub fn new<S: State>(mdp: &Mdp<S>) -> Self {
+        let actions = (0..mdp.states().len())
+            .map(|i| {
+                let mut actions = mdp.actions(i).keys().cloned().collect::<Vec<_>>();
+                actions.sort_by_key(|a| a.sort_key());
+                actions
+            })
+            .collect::<Vec<Vec<_>>>();
+        let outcomes = actions
+            .iter()
+            .enumerate()
+            .map(|(i, actions)| {
+                actions
+                    .iter()
+                    .map(|action| {
+                        mdp.actions(i)[action]
+                            .iter()
+                            .map(|t| (t.to(), t.probability(), t.reward()))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        Self {
+            actions,
+            outcomes,
+            state: 0,
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.actions.len()
+    }
+
+    pub fn state(&self) -> usize {
+        self.state
+    }
+
+    /// The actions available in the current state.
+    pub fn actions(&self) -> &[ActionBox] {
+        self.actions[self.state].as_ref()
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.actions[self.state].is_empty()
+    }
+
+    /// Starts a new episode in the initial state.
+    pub fn reset(&mut self) -> usize {
+        self.reset_to(0)
+    }
+
+    pub fn reset_to(&mut self, state: usize) -> usize {
+        self.state = state;
+        state
+    }
+
+    /// Takes the action with the given index in `actions()`.
+    pub fn step(&mut self, action: usize) -> Step {
+        let outcomes = &self.outcomes[self.state][action];
+        let total = outcomes.iter().map(|o| o.1).sum::<f64>();
+        let mut remaining = self.rng.next_f64() * total;
+        let &(next, _, reward) = outcomes
+            .iter()
+            .find(|o| {
+                remaining -= o.1;
+                remaining < 0.0
+            })
+            .unwrap_or_else(|| outcomes.last().unwrap());
+
+        self.state = next;
+        Step {
+            reward,
+            state: next,
+            terminal: self.is_terminal(),
+        }
+    }
+}
+
+/// How an agent picks actions while it learns.
+#[derive(Debug, Clone, Copy)]
+pub enum Exploration {
+    /// A uniformly random action with this probability, otherwise a greedy one.
+    EpsilonGreedy(f64),
+    /// Softmax over the Q-values with this temperature.
+    Boltzmann(f64),
+}
+
+impl Exploration {
+    /// The probability of picking each action, given their Q-values.
+    pub fn probabilities(&self, q_values: &[f64]) -> Vec<f64> {
+        let n = q_values.len();
+        let best = q_values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        match *self {
+            Exploration::EpsilonGreedy(epsilon) => {
+                let ties = q_values.iter().filter(|&&q| q == best).count();
+                q_values
+                    .iter()
+                    .map(|&q| {
+                        let greedy = if q == best { 1.0 / ties as f64 } else { 0.0 };
+                        epsilon / n as f64 + (1.0 - epsilon) * greedy
+                    })
+                    .collect()
+            }
+            Exploration::Boltzmann(temperature) => {
+                let weights = q_values
+                    .iter()
+                    .map(|&q| ((q - best) / temperature).exp())
+                    .collect::<Vec<_>>();
+                let total = weights.iter().sum::<f64>();
+                weights.into_iter().map(|w| w / total).collect()
+            }
+        }
+    }
+
+    pub(crate) fn choose(&self, q_values: &[f64], rng: &mut Rng) -> usize {
+        let probabilities = self.probabilities(q_values);
+        let mut remaining = rng.next_f64();
+        probabilities
+            .iter()
+            .position(|p| {
+                remaining -= p;
+                remaining < 0.0
+            })
+            .unwrap_or(probabilities.len() - 1)
+    }
+}
+
+/// The step size of every update.
+#[derive(Debug, Clone, Copy)]
+pub enum LearningRate {
+    Constant(f64),
+    /// `1 / n^exponent`, where `n` counts the updates of the state-action pair so far.
+    /// Exponents in `(0.5, 1]` meet the usual conditions for convergence.
+    Polynomial(f64),
+}
+
+impl LearningRate {
+    pub(crate) fn rate(&self, updates: usize) -> f64 {
+        match *self {
+            LearningRate::Constant(rate) => rate,
+            LearningRate::Polynomial(exponent) => 1.0 / (updates as f64).powf(exponent),
+        }
+    }
+}
+
+/// Learned action values, for the states the agent has seen.
+#[derive(Debug, Clone, Default)]
+pub struct QTable {
+    actions: Vec<Vec<ActionBox>>,
+    values: Vec<Vec<f64>>,
+    updates: Vec<Vec<usize>>,
+}
+
+impl QTable {
+    /// The Q-values of a state, in the simulator's action order. Empty if it hasn't been seen.
+    pub fn q_values(&self, state: usize) -> &[f64] {
+        self.values[state].as_ref()
+    }
+
+    /// The best Q-value of every state, or 0 where there is none, laid out like the values
+    /// of a `Solution`.
+    pub fn values(&self) -> Vec<f64> {
+        (0..self.values.len()).map(|i| self.max(i)).collect()
+    }
+
+    /// The greedy policy. States that were never seen get no action.
+    pub fn policy(&self) -> Policy {
+        let actions = (0..self.values.len())
+            .map(|i| self.greedy(i).map(|a| self.actions[i][a].clone()))
+            .collect();
+        Policy::new(actions)
+    }
+
+    /// The policy an agent follows while it explores, e.g. the softmax over the Q-values for
+    /// `Exploration::Boltzmann`. States that were never seen get no actions.
+    pub fn exploring_policy(&self, exploration: Exploration) -> StochasticPolicy {
+        let distributions = (0..self.values.len())
+            .map(|i| {
+                let probabilities = exploration.probabilities(&self.values[i]);
+                self.actions[i].iter().cloned().zip(probabilities).collect()
+            })
+            .collect();
+        StochasticPolicy::new(distributions)
+    }
+
+    // Makes room for every state of the simulator.
+    pub(crate) fn resize(&mut self, states: usize) {
+        if self.values.len() < states {
+            self.actions.resize(states, vec![]);
+            self.values.resize(states, vec![]);
+            self.updates.resize(states, vec![]);
+        }
+    }
+
+    // Called whenever the agent lands in a state, so its actions are known from then on.
+    pub(crate) fn observe(&mut self, state: usize, actions: &[ActionBox]) {
+        if self.actions[state].len() != actions.len() {
+            self.actions[state] = actions.to_vec();
+            self.values[state] = vec![0.0; actions.len()];
+            self.updates[state] = vec![0; actions.len()];
+        }
+    }
+
+    pub(crate) fn action(&self, state: usize, action: usize) -> &ActionBox {
+        &self.actions[state][action]
+    }
+
+    pub(crate) fn greedy(&self, state: usize) -> Option<usize> {
+        (0..self.values[state].len())
+            .map(|a| (self.values[state][a], a))
+            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+            .map(|(_, a)| a)
+    }
+
+    pub(crate) fn max(&self, state: usize) -> f64 {
+        match self.greedy(state) {
+            Some(a) => self.values[state][a],
+            None => 0.0,
+        }
+    }
+
+    pub(crate) fn set(&mut self, state: usize, action: usize, value: f64) {
+        self.values[state][action] = value;
+    }
+
+    // Moves the Q-value towards the target and returns the new value.
+    pub(crate) fn update(
+        &mut self,
+        state: usize,
+        action: usize,
+        target: f64,
+        learning_rate: LearningRate,
+    ) -> f64 {
+        self.updates[state][action] += 1;
+        let rate = learning_rate.rate(self.updates[state][action]);
+        let q = &mut self.values[state][action];
+        *q += rate * (target - *q);
+        *q
+    }
+}
+
+/// Exploration, learning rate and episode settings shared by all agents.
+#[derive(Debug, Clone, Copy)]
+pub struct LearningConfig {
+    exploration: Exploration,
+    learning_rate: LearningRate,
+    max_steps: usize,
+    seed: u64,
+}
+
+impl Default for LearningConfig {
+    fn default() -> Self {
+        Self {
+            exploration: Exploration::EpsilonGreedy(0.1),
+            learning_rate: LearningRate::Constant(0.1),
+            max_steps: 1000,
+            seed: 0,
+        }
+    }
+}
+
+impl LearningConfig {
+    pub fn exploration(mut self, exploration: Exploration) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    pub fn learning_rate(mut self, learning_rate: LearningRate) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// Episodes are cut off after this many steps, for models that never terminate.
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Seed for the agent's own choices. The simulator has a separate seed.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// A learning agent that only interacts with a `Simulator`.
+pub trait Agent {
+    /// Runs one episode from the initial state and returns its total (undiscounted) reward.
+    fn episode(&mut self, simulator: &mut Simulator) -> f64;
+
+    fn q_table(&self) -> &QTable;
+
+    /// Runs a number of episodes and returns the total reward of each one.
+    fn train(&mut self, simulator: &mut Simulator, episodes: usize) -> Vec<f64> {
+        (0..episodes).map(|_| self.episode(simulator)).collect()
+    }
+
+    fn policy(&self) -> Policy {
+        self.q_table().policy()
+    }
+}
@@ -0,0 +1,86 @@
+use crate::rng::Rng;
+
+use super::{Agent, LearningConfig, QTable, Simulator};
+
+/// Double Q-learning (van Hasselt).
+///
+/// Keeps two independent estimates and on every step updates one of them at random, picking
+/// the best next action with that estimate but valuing it with the other. This removes the
+/// maximisation bias of plain Q-learning on noisy rewards. It explores and acts on the average
+/// of both estimates, which is what `q_table()` returns.
+pub struct DoubleQLearningAgent {
+    discount: f64,
+    config: LearningConfig,
+    estimates: [QTable; 2],
+    table: QTable,
+    rng: Rng,
+}
+
+impl DoubleQLearningAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            estimates: [QTable::default(), QTable::default()],
+            table: QTable::default(),
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+
+    fn observe(&mut self, simulator: &Simulator) {
+        let state = simulator.state();
+        for table in self.estimates.iter_mut().chain([&mut self.table]) {
+            table.observe(state, simulator.actions());
+        }
+    }
+}
+
+impl Agent for DoubleQLearningAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        for table in self.estimates.iter_mut().chain([&mut self.table]) {
+            table.resize(simulator.num_states());
+        }
+        let mut state = simulator.reset();
+        self.observe(simulator);
+
+        let mut total = 0.0;
+        for _ in 0..self.config.max_steps {
+            if simulator.is_terminal() {
+                break;
+            }
+            let action = self
+                .config
+                .exploration
+                .choose(self.table.q_values(state), &mut self.rng);
+            let step = simulator.step(action);
+            total += step.reward();
+            self.observe(simulator);
+
+            let updated = self.rng.below(2);
+            let other = &self.estimates[1 - updated];
+            let future = self.estimates[updated]
+                .greedy(step.state())
+                .map_or(0.0, |a| other.q_values(step.state())[a]);
+            let target = step.reward() + self.discount * future;
+            self.estimates[updated].update(state, action, target, self.config.learning_rate);
+
+            let average = (self.estimates[0].q_values(state)[action]
+                + self.estimates[1].q_values(state)[action])
+                / 2.0;
+            self.table.set(state, action, average);
+            state = step.state();
+        }
+        total
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use crate::{
+    mdp::{Mdp, Transition},
+    model::State,
+    rng::Rng,
+};
+
+use super::{Agent, LearningConfig, QTable, Simulator};
+
+// How often an action led to a next state, and the total reward it gave on the way.
+#[derive(Debug, Clone)]
+struct Outcome {
+    state: usize,
+    count: usize,
+    reward: f64,
+}
+
+/// Dyna-Q: Q-learning plus planning on a model learned from experience.
+///
+/// Every real step updates `Q(s, a)` like `QLearningAgent` and is recorded in an empirical
+/// model, which counts the next states and averages the rewards of every state-action pair.
+/// After each real step the agent runs `planning_steps` simulated Q-learning backups, each on
+/// a random pair it has tried before, with an outcome sampled from the model.
+///
+/// `learned_mdp` exports the model as an ordinary `Mdp`, so any solver can be run on it.
+pub struct DynaQAgent {
+    discount: f64,
+    config: LearningConfig,
+    planning_steps: usize,
+    table: QTable,
+    // Outcomes per state and action, in the simulator's action order.
+    model: Vec<Vec<Vec<Outcome>>>,
+    // Every state-action pair in the model, in the order they were first tried.
+    pairs: Vec<(usize, usize)>,
+    rng: Rng,
+}
+
+impl DynaQAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            planning_steps: 5,
+            table: QTable::default(),
+            model: vec![],
+            pairs: vec![],
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+
+    /// Simulated backups after every real step. 0 makes it plain Q-learning.
+    pub fn planning_steps(mut self, planning_steps: usize) -> Self {
+        self.planning_steps = planning_steps;
+        self
+    }
+
+    /// The number of state-action pairs the agent has tried.
+    pub fn known_pairs(&self) -> usize {
+        self.pairs.len()
+    }
+
+    /// The learned model as an `Mdp` over the given states, which should be the states of
+    /// the model the simulator was built from (`Mdp::states`), so the indices line up.
+    ///
+    /// Probabilities are the observed frequencies and rewards the average observed reward of
+    /// each transition. Actions that were never tried are left out, so states the agent never
+    /// acted in are terminal.
+    pub fn learned_mdp<S: State>(&self, states: &[S]) -> Mdp<S> {
+        let actions_from_states = (0..states.len())
+            .map(|i| {
+                let mut actions = HashMap::new();
+                let tried = self.model.get(i).map(|m| m.as_slice()).unwrap_or_default();
+                for (a, outcomes) in tried.iter().enumerate() {
+                    let total = outcomes.iter().map(|o| o.count).sum::<usize>();
+                    if total == 0 {
+                        continue;
+                    }
+                    let action = self.table.action(i, a);
+                    let transitions = outcomes
+                        .iter()
+                        .map(|o| {
+                            Transition::new(
+                                i,
+                                o.state,
+                                o.reward / o.count as f64,
+                                o.count as f64 / total as f64,
+                                action.clone(),
+                            )
+                        })
+                        .collect();
+                    actions.insert(action.clone(), transitions);
+                }
+                actions
+            })
+            .collect();
+        Mdp::from_transitions(states.to_vec(), actions_from_states)
+    }
+
+    fn record(&mut self, state: usize, action: usize, next: usize, reward: f64) {
+        let actions = self.table.q_values(state).len();
+        if self.model[state].len() != actions {
+            self.model[state] = vec![vec![]; actions];
+        }
+        let outcomes = &mut self.model[state][action];
+        if outcomes.is_empty() {
+            self.pairs.push((state, action));
+        }
+        match outcomes.iter_mut().find(|o| o.state == next) {
+            Some(outcome) => {
+                outcome.count += 1;
+                outcome.reward += reward;
+            }
+            None => outcomes.push(Outcome {
+                state: next,
+                count: 1,
+                reward,
+            }),
+        }
+    }
+
+    fn plan(&mut self) {
+        if self.pairs.is_empty() {
+            return;
+        }
+        for _ in 0..self.planning_steps {
+            let (state, action) = self.pairs[self.rng.below(self.pairs.len())];
+            let outcomes = &self.model[state][action];
+            let total = outcomes.iter().map(|o| o.count).sum::<usize>();
+            let mut remaining = self.rng.below(total);
+            let outcome = outcomes
+                .iter()
+                .find(|o| {
+                    if remaining < o.count {
+                        return true;
+                    }
+                    remaining -= o.count;
+                    false
+                })
+                .unwrap();
+
+            let reward = outcome.reward / outcome.count as f64;
+            let target = reward + self.discount * self.table.max(outcome.state);
+            self.table
+                .update(state, action, target, self.config.learning_rate);
+        }
+    }
+}
+
+impl Agent for DynaQAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        self.table.resize(simulator.num_states());
+        if self.model.len() < simulator.num_states() {
+            self.model.resize(simulator.num_states(), vec![]);
+        }
+        let mut state = simulator.reset();
+        self.table.observe(state, simulator.actions());
+
+        let mut total = 0.0;
+        for _ in 0..self.config.max_steps {
+            if simulator.is_terminal() {
+                break;
+            }
+            let action = self
+                .config
+                .exploration
+                .choose(self.table.q_values(state), &mut self.rng);
+            let step = simulator.step(action);
+            total += step.reward();
+            self.table.observe(step.state(), simulator.actions());
+
+            let target = step.reward() + self.discount * self.table.max(step.state());
+            self.table
+                .update(state, action, target, self.config.learning_rate);
+            self.record(state, action, step.state(), step.reward());
+            self.plan();
+            state = step.state();
+        }
+        total
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
@@ -0,0 +1,330 @@
+use std::{collections::HashSet, hash::Hash};
+
+use crate::{policy::Policy, rng::Rng};
+
+use super::{policy_action, start, Agent, LearningConfig, QTable, Simulator};
+
+/// Which occurrences of a state in an episode produce a sample of its return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visits {
+    First,
+    Every,
+}
+
+/// How off-policy returns are reweighted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportanceSampling {
+    /// Plain average of the weighted returns. Unbiased, but the variance can be huge.
+    Ordinary,
+    /// Divides by the sum of the weights instead. Biased, but far more stable.
+    Weighted,
+}
+
+// Which steps of an episode count as a visit, given what was visited at every step.
+fn counted_steps<T: Hash + Eq>(visited: &[T], visits: Visits) -> Vec<bool> {
+    match visits {
+        Visits::Every => vec![true; visited.len()],
+        Visits::First => {
+            let mut seen = HashSet::new();
+            visited.iter().map(|v| seen.insert(v)).collect()
+        }
+    }
+}
+
+/// Monte Carlo prediction of the state values of a `Policy`, from sampled episodes.
+///
+/// The estimates come back laid out like the values of a `Solution`, so they can be compared
+/// to an exact solver directly. States that are never visited (or have no actions) get 0,
+/// so use exploring starts to cover states the policy doesn't reach from the initial state.
+/// Every call starts from scratch. Episodes are cut off after `max_steps`, and the steps of a
+/// cut off episode only count if the discount makes what's missing from their return small
+/// (see `tail_tolerance`), so continuing tasks need `max_steps` well past that horizon.
+pub struct MonteCarloPrediction {
+    discount: f64,
+    visits: Visits,
+    importance_sampling: ImportanceSampling,
+    exploring_starts: bool,
+    max_steps: usize,
+    tail_tolerance: f64,
+    rng: Rng,
+}
+
+impl MonteCarloPrediction {
+    pub fn new(discount: f64) -> Self {
+        Self {
+            discount,
+            visits: Visits::First,
+            importance_sampling: ImportanceSampling::Weighted,
+            exploring_starts: false,
+            max_steps: 1000,
+            tail_tolerance: 1e-3,
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn visits(mut self, visits: Visits) -> Self {
+        self.visits = visits;
+        self
+    }
+
+    /// Only used by `evaluate_off_policy`.
+    pub fn importance_sampling(mut self, importance_sampling: ImportanceSampling) -> Self {
+        self.importance_sampling = importance_sampling;
+        self
+    }
+
+    /// Starts every episode in a uniformly random state instead of the initial state.
+    pub fn exploring_starts(mut self, exploring_starts: bool) -> Self {
+        self.exploring_starts = exploring_starts;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// A step `t` of an episode cut off after `T` steps only counts if `discount^(T - t)`,
+    /// the weight of the return it never saw, is at most this.
+    pub fn tail_tolerance(mut self, tail_tolerance: f64) -> Self {
+        self.tail_tolerance = tail_tolerance;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// On-policy: follows `policy` and averages the returns. States without an action in
+    /// the policy act uniformly at random.
+    pub fn evaluate(
+        &mut self,
+        simulator: &mut Simulator,
+        policy: &Policy,
+        episodes: usize,
+    ) -> Vec<f64> {
+        let mut sums = vec![0.0; simulator.num_states()];
+        let mut counts = vec![0; simulator.num_states()];
+        for _ in 0..episodes {
+            let (states, rewards) = self.sample(simulator, policy);
+            let counted = self.counted_steps(&states, simulator);
+            let mut ret = 0.0;
+            for t in (0..states.len()).rev() {
+                ret = rewards[t] + self.discount * ret;
+                if counted[t] {
+                    sums[states[t]] += ret;
+                    counts[states[t]] += 1;
+                }
+            }
+        }
+
+        sums.iter()
+            .zip(counts)
+            .map(|(sum, count)| if count == 0 { 0.0 } else { sum / count as f64 })
+            .collect()
+    }
+
+    /// Off-policy: estimates the values of the deterministic `target` policy from episodes
+    /// that follow `behaviour` with probability `1 - epsilon` and pick uniformly otherwise.
+    /// `epsilon` has to be positive wherever the two policies disagree.
+    pub fn evaluate_off_policy(
+        &mut self,
+        simulator: &mut Simulator,
+        target: &Policy,
+        behaviour: &Policy,
+        epsilon: f64,
+        episodes: usize,
+    ) -> Vec<f64> {
+        let mut values = vec![0.0; simulator.num_states()];
+        // Sum of the weights for weighted sampling, number of samples for ordinary sampling.
+        let mut weights = vec![0.0; simulator.num_states()];
+        for _ in 0..episodes {
+            let (states, ratios, rewards) =
+                self.sample_off_policy(simulator, target, behaviour, epsilon);
+            let counted = self.counted_steps(&states, simulator);
+            let mut ret = 0.0;
+            let mut ratio = 1.0;
+            for t in (0..states.len()).rev() {
+                ret = rewards[t] + self.discount * ret;
+                ratio *= ratios[t];
+                if !counted[t] {
+                    continue;
+                }
+                let state = states[t];
+                match self.importance_sampling {
+                    ImportanceSampling::Ordinary => {
+                        weights[state] += 1.0;
+                        values[state] += (ratio * ret - values[state]) / weights[state];
+                    }
+                    ImportanceSampling::Weighted if ratio > 0.0 => {
+                        weights[state] += ratio;
+                        values[state] += ratio / weights[state] * (ret - values[state]);
+                    }
+                    ImportanceSampling::Weighted => {}
+                }
+            }
+        }
+        values
+    }
+
+    // Like `counted_steps`, leaving out the steps near the end of an episode that was cut
+    // off before reaching a terminal state.
+    fn counted_steps(&self, states: &[usize], simulator: &Simulator) -> Vec<bool> {
+        let mut counted = counted_steps(states, self.visits);
+        if !simulator.is_terminal() {
+            let mut weight = self.discount;
+            for count in counted.iter_mut().rev() {
+                if weight <= self.tail_tolerance {
+                    break;
+                }
+                *count = false;
+                weight *= self.discount;
+            }
+        }
+        counted
+    }
+
+    // One episode following the policy, as the visited states and the rewards.
+    fn sample(&mut self, simulator: &mut Simulator, policy: &Policy) -> (Vec<usize>, Vec<f64>) {
+        let mut states = vec![];
+        let mut rewards = vec![];
+        start(simulator, self.exploring_starts, &mut self.rng);
+        while !simulator.is_terminal() && states.len() < self.max_steps {
+            let action = policy_action(simulator, policy)
+                .unwrap_or_else(|| self.rng.below(simulator.actions().len()));
+            states.push(simulator.state());
+            rewards.push(simulator.step(action).reward());
+        }
+        (states, rewards)
+    }
+
+    // Like `sample`, but also returns the `target / behaviour` probability ratio of every
+    // action taken.
+    fn sample_off_policy(
+        &mut self,
+        simulator: &mut Simulator,
+        target: &Policy,
+        behaviour: &Policy,
+        epsilon: f64,
+    ) -> (Vec<usize>, Vec<f64>, Vec<f64>) {
+        let mut states = vec![];
+        let mut ratios = vec![];
+        let mut rewards = vec![];
+        start(simulator, self.exploring_starts, &mut self.rng);
+        while !simulator.is_terminal() && states.len() < self.max_steps {
+            let n = simulator.actions().len() as f64;
+            let preferred = policy_action(simulator, behaviour);
+            let action = match preferred {
+                Some(action) if self.rng.next_f64() >= epsilon => action,
+                _ => self.rng.below(n as usize),
+            };
+            let behaviour_probability = match preferred {
+                Some(p) if p == action => 1.0 - epsilon + epsilon / n,
+                Some(_) => epsilon / n,
+                None => 1.0 / n,
+            };
+            let target_probability = match policy_action(simulator, target) {
+                Some(t) if t == action => 1.0,
+                Some(_) => 0.0,
+                None => 1.0 / n,
+            };
+
+            states.push(simulator.state());
+            ratios.push(target_probability / behaviour_probability);
+            rewards.push(simulator.step(action).reward());
+        }
+        (states, ratios, rewards)
+    }
+}
+
+/// On-policy Monte Carlo control.
+///
+/// Plays whole episodes with `LearningConfig::exploration` and then moves every visited
+/// `Q(s, a)` towards the return that followed it. `LearningRate::Polynomial(1.0)` gives the
+/// textbook sample averages.
+///
+/// With exploring starts every episode begins in a random state with a random action and is
+/// greedy from then on, which keeps every pair visited without an exploring policy.
+pub struct MonteCarloControlAgent {
+    discount: f64,
+    config: LearningConfig,
+    visits: Visits,
+    exploring_starts: bool,
+    table: QTable,
+    rng: Rng,
+}
+
+impl MonteCarloControlAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            visits: Visits::First,
+            exploring_starts: false,
+            table: QTable::default(),
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+
+    pub fn visits(mut self, visits: Visits) -> Self {
+        self.visits = visits;
+        self
+    }
+
+    pub fn exploring_starts(mut self, exploring_starts: bool) -> Self {
+        self.exploring_starts = exploring_starts;
+        self
+    }
+}
+
+impl Agent for MonteCarloControlAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        self.table.resize(simulator.num_states());
+        start(simulator, self.exploring_starts, &mut self.rng);
+
+        let mut states = vec![];
+        let mut actions = vec![];
+        let mut rewards = vec![];
+        while !simulator.is_terminal() && states.len() < self.config.max_steps {
+            let state = simulator.state();
+            self.table.observe(state, simulator.actions());
+            let action = if self.exploring_starts && states.is_empty() {
+                self.rng.below(simulator.actions().len())
+            } else if self.exploring_starts {
+                self.table.greedy(state).unwrap()
+            } else {
+                self.config
+                    .exploration
+                    .choose(self.table.q_values(state), &mut self.rng)
+            };
+            states.push(state);
+            actions.push(action);
+            rewards.push(simulator.step(action).reward());
+        }
+
+        // Pairs rather than states decide what counts as a first visit.
+        let pairs = states.iter().zip(actions.iter()).collect::<Vec<_>>();
+        let counted = counted_steps(&pairs, self.visits);
+        let mut ret = 0.0;
+        for t in (0..states.len()).rev() {
+            ret = rewards[t] + self.discount * ret;
+            if counted[t] {
+                self.table
+                    .update(states[t], actions[t], ret, self.config.learning_rate);
+            }
+        }
+        rewards.iter().sum()
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
@@ -0,0 +1,65 @@
+use crate::rng::Rng;
+
+use super::{Agent, LearningConfig, QTable, Simulator};
+
+/// Tabular Q-learning.
+///
+/// Off-policy: it explores according to `LearningConfig::exploration`, but every update moves
+/// `Q(s, a)` towards `r + discount * max_a' Q(s', a')`, so it learns the greedy policy.
+/// Q-values start at 0.
+pub struct QLearningAgent {
+    discount: f64,
+    config: LearningConfig,
+    table: QTable,
+    rng: Rng,
+}
+
+impl QLearningAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            table: QTable::default(),
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+}
+
+impl Agent for QLearningAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        self.table.resize(simulator.num_states());
+        let mut state = simulator.reset();
+        self.table.observe(state, simulator.actions());
+
+        let mut total = 0.0;
+        for _ in 0..self.config.max_steps {
+            if simulator.is_terminal() {
+                break;
+            }
+            let action = self
+                .config
+                .exploration
+                .choose(self.table.q_values(state), &mut self.rng);
+            let step = simulator.step(action);
+            total += step.reward();
+            self.table.observe(step.state(), simulator.actions());
+
+            let target = step.reward() + self.discount * self.table.max(step.state());
+            self.table
+                .update(state, action, target, self.config.learning_rate);
+            state = step.state();
+        }
+        total
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
@@ -0,0 +1,145 @@
+use crate::rng::Rng;
+
+use super::{Agent, LearningConfig, QTable, Simulator};
+
+/// On-policy SARSA.
+///
+/// Updates `Q(s, a)` towards `r + discount * Q(s', a')`, where `a'` is the action it actually
+/// takes next. It learns the value of the exploring policy, so with epsilon-greedy exploration
+/// it keeps a safe distance from big penalties (the path along the cliff in `cliff_walking`).
+pub struct SarsaAgent {
+    discount: f64,
+    config: LearningConfig,
+    table: QTable,
+    rng: Rng,
+}
+
+impl SarsaAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            table: QTable::default(),
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+
+    fn choose(&mut self, state: usize, simulator: &Simulator) -> Option<usize> {
+        if simulator.is_terminal() {
+            return None;
+        }
+        let q_values = self.table.q_values(state);
+        Some(self.config.exploration.choose(q_values, &mut self.rng))
+    }
+}
+
+impl Agent for SarsaAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        self.table.resize(simulator.num_states());
+        let mut state = simulator.reset();
+        self.table.observe(state, simulator.actions());
+        let mut action = self.choose(state, simulator);
+
+        let mut total = 0.0;
+        for _ in 0..self.config.max_steps {
+            let Some(current) = action else {
+                break;
+            };
+            let step = simulator.step(current);
+            total += step.reward();
+            self.table.observe(step.state(), simulator.actions());
+
+            let next = self.choose(step.state(), simulator);
+            let future = next.map_or(0.0, |a| self.table.q_values(step.state())[a]);
+            let target = step.reward() + self.discount * future;
+            self.table
+                .update(state, current, target, self.config.learning_rate);
+            state = step.state();
+            action = next;
+        }
+        total
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
+
+/// Expected SARSA.
+///
+/// Like `SarsaAgent`, but the target averages `Q(s', a')` over the exploration probabilities
+/// instead of using the sampled next action, which takes out that source of variance.
+pub struct ExpectedSarsaAgent {
+    discount: f64,
+    config: LearningConfig,
+    table: QTable,
+    rng: Rng,
+}
+
+impl ExpectedSarsaAgent {
+    pub fn new(discount: f64) -> Self {
+        let config = LearningConfig::default();
+        Self {
+            discount,
+            config,
+            table: QTable::default(),
+            rng: Rng::new(config.seed),
+        }
+    }
+
+    pub fn with_config(mut self, config: LearningConfig) -> Self {
+        self.config = config;
+        self.rng = Rng::new(config.seed);
+        self
+    }
+
+    fn expected(&self, state: usize) -> f64 {
+        let q_values = self.table.q_values(state);
+        self.config
+            .exploration
+            .probabilities(q_values)
+            .iter()
+            .zip(q_values)
+            .map(|(p, q)| p * q)
+            .sum()
+    }
+}
+
+impl Agent for ExpectedSarsaAgent {
+    fn episode(&mut self, simulator: &mut Simulator) -> f64 {
+        self.table.resize(simulator.num_states());
+        let mut state = simulator.reset();
+        self.table.observe(state, simulator.actions());
+
+        let mut total = 0.0;
+        for _ in 0..self.config.max_steps {
+            if simulator.is_terminal() {
+                break;
+            }
+            let action = self
+                .config
+                .exploration
+                .choose(self.table.q_values(state), &mut self.rng);
+            let step = simulator.step(action);
+            total += step.reward();
+            self.table.observe(step.state(), simulator.actions());
+
+            let target = step.reward() + self.discount * self.expected(step.state());
+            self.table
+                .update(state, action, target, self.config.learning_rate);
+            state = step.state();
+        }
+        total
+    }
+
+    fn q_table(&self) -> &QTable {
+        &self.table
+    }
+}
@@ -0,0 +1,204 @@
+use crate::{
+    mdp::Mdp,
+    model::State,
+    policy::{Policy, StochasticPolicy},
+    rng::Rng,
+    solver::evaluate_stochastic_policy,
+};
+
+use super::{policy_action, start, LearningRate, Simulator};
+
+/// How eligibility traces grow when a state is visited again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trace {
+    /// Adds 1 on every visit.
+    Accumulating,
+    /// Resets the trace to 1.
+    Replacing,
+}
+
+/// TD(lambda) prediction of the state values of a fixed `Policy`.
+///
+/// Uses the backward view: after every step the TD error
+/// `r + discount * V(s') - V(s)` is applied to every state in proportion to its eligibility
+/// trace, and the traces decay by `discount * lambda`. A `lambda` of 0 is plain TD(0), and 1
+/// behaves like every-visit Monte Carlo with online updates.
+///
+/// Like `MonteCarloPrediction`, the estimates are laid out like the values of a `Solution`
+/// and every call starts from scratch. States without an action in the policy act uniformly
+/// at random.
+pub struct TdPrediction {
+    discount: f64,
+    lambda: f64,
+    trace: Trace,
+    learning_rate: LearningRate,
+    initial_value: f64,
+    exploring_starts: bool,
+    max_steps: usize,
+    rng: Rng,
+}
+
+impl TdPrediction {
+    pub fn new(discount: f64) -> Self {
+        Self {
+            discount,
+            lambda: 0.0,
+            trace: Trace::Accumulating,
+            learning_rate: LearningRate::Constant(0.1),
+            initial_value: 0.0,
+            exploring_starts: false,
+            max_steps: 1000,
+            rng: Rng::new(0),
+        }
+    }
+
+    pub fn lambda(mut self, lambda: f64) -> Self {
+        self.lambda = lambda;
+        self
+    }
+
+    pub fn trace(mut self, trace: Trace) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Schedules count the visits of each state.
+    pub fn learning_rate(mut self, learning_rate: LearningRate) -> Self {
+        self.learning_rate = learning_rate;
+        self
+    }
+
+    /// What every state that isn't terminal starts out with.
+    pub fn initial_value(mut self, initial_value: f64) -> Self {
+        self.initial_value = initial_value;
+        self
+    }
+
+    /// Starts every episode in a uniformly random state instead of the initial state.
+    pub fn exploring_starts(mut self, exploring_starts: bool) -> Self {
+        self.exploring_starts = exploring_starts;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn evaluate(
+        &mut self,
+        simulator: &mut Simulator,
+        policy: &Policy,
+        episodes: usize,
+    ) -> Vec<f64> {
+        let mut estimates = Estimates::new(simulator, self.initial_value);
+        for _ in 0..episodes {
+            self.episode(simulator, policy, &mut estimates);
+        }
+        estimates.values
+    }
+
+    /// Learns like `evaluate`, but returns the root mean squared error against the exact
+    /// values of the policy after every episode. Only states with actions are counted, and
+    /// states without an action in the policy act uniformly at random, like in the episodes.
+    pub fn rms_errors<S: State>(
+        &mut self,
+        mdp: &Mdp<S>,
+        simulator: &mut Simulator,
+        policy: &Policy,
+        episodes: usize,
+    ) -> Vec<f64> {
+        let uniform = StochasticPolicy::uniform(mdp);
+        let distributions = policy
+            .actions()
+            .iter()
+            .zip(uniform.distributions())
+            .map(|(action, row)| match action {
+                Some(action) => vec![(action.clone(), 1.0)],
+                None => row.clone(),
+            })
+            .collect::<Vec<_>>();
+        let exact = evaluate_stochastic_policy(mdp, &distributions, self.discount);
+        let states = (0..exact.len())
+            .filter(|&i| !mdp.actions(i).is_empty())
+            .collect::<Vec<_>>();
+
+        let mut estimates = Estimates::new(simulator, self.initial_value);
+        (0..episodes)
+            .map(|_| {
+                self.episode(simulator, policy, &mut estimates);
+                let squared = states
+                    .iter()
+                    .map(|&i| (estimates.values[i] - exact[i]).powi(2))
+                    .sum::<f64>();
+                (squared / states.len().max(1) as f64).sqrt()
+            })
+            .collect()
+    }
+
+    fn episode(&mut self, simulator: &mut Simulator, policy: &Policy, estimates: &mut Estimates) {
+        let mut traces = vec![0.0; estimates.values.len()];
+        // The states with a trace, so every step doesn't have to go over all of them.
+        let mut traced = vec![];
+
+        start(simulator, self.exploring_starts, &mut self.rng);
+        for _ in 0..self.max_steps {
+            if simulator.is_terminal() {
+                break;
+            }
+            let state = simulator.state();
+            let action = policy_action(simulator, policy)
+                .unwrap_or_else(|| self.rng.below(simulator.actions().len()));
+            let step = simulator.step(action);
+            let error = step.reward() + self.discount * estimates.values[step.state()]
+                - estimates.values[state];
+
+            if traces[state] == 0.0 {
+                traced.push(state);
+            }
+            traces[state] = match self.trace {
+                Trace::Accumulating => traces[state] + 1.0,
+                Trace::Replacing => 1.0,
+            };
+            estimates.visits[state] += 1;
+
+            for &i in traced.iter() {
+                let rate = self.learning_rate.rate(estimates.visits[i]);
+                estimates.values[i] += rate * error * traces[i];
+                traces[i] *= self.discount * self.lambda;
+            }
+            traced.retain(|&i| traces[i] != 0.0);
+        }
+    }
+}
+
+struct Estimates {
+    values: Vec<f64>,
+    visits: Vec<usize>,
+}
+
+impl Estimates {
+    // Terminal states are always worth 0.
+    fn new(simulator: &mut Simulator, initial_value: f64) -> Self {
+        let n = simulator.num_states();
+        let values = (0..n)
+            .map(|i| {
+                simulator.reset_to(i);
+                if simulator.is_terminal() {
+                    0.0
+                } else {
+                    initial_value
+                }
+            })
+            .collect();
+        Self {
+            values,
+            visits: vec![0; n],
+        }
+    }
+}
@@ -1,4 +1,14 @@
+pub mod constrained;
+pub mod game;
+pub mod graph;
+pub mod learning;
+pub mod linalg;
+pub mod lp;
 pub mod mdp;
 pub mod model;
+pub mod multi_objective;
+pub mod planner;
 pub mod policy;
+pub mod pomdp;
+pub mod rng;
 pub mod solver;
@@ -0,0 +1,319 @@
+use std::{error::Error, fmt::Display};
+
+const EPSILON: f64 = 1e-9;
+// Switch to Bland's rule after this many pivots in a row that don't improve the objective.
+const DEGENERATE_PIVOTS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    LessEqual,
+    Equal,
+    GreaterEqual,
+}
+
+impl Relation {
+    fn flipped(self) -> Self {
+        match self {
+            Relation::LessEqual => Relation::GreaterEqual,
+            Relation::Equal => Relation::Equal,
+            Relation::GreaterEqual => Relation::LessEqual,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LpError {
+    Infeasible,
+    Unbounded,
+}
+
+impl Display for LpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LpError::Infeasible => write!(f, "linear program is infeasible"),
+            LpError::Unbounded => write!(f, "linear program is unbounded"),
+        }
+    }
+}
+
+impl Error for LpError {}
+
+/// Maximise (or minimise) `objective · x` subject to linear constraints and `x >= 0`.
+/// Solved with a dense two-phase simplex, so it is meant for small and medium problems.
+#[derive(Debug, Clone)]
+pub struct LinearProgram {
+    objective: Vec<f64>,
+    constraints: Vec<(Vec<f64>, Relation, f64)>,
+    // -1 when minimising, which is solved as maximising the negated objective.
+    sense: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LpSolution {
+    variables: Vec<f64>,
+    objective: f64,
+    duals: Vec<f64>,
+    pivots: usize,
+}
+
+impl LpSolution {
+    pub fn variables(&self) -> &[f64] {
+        self.variables.as_ref()
+    }
+
+    pub fn objective(&self) -> f64 {
+        self.objective
+    }
+
+    /// Shadow price of each constraint, in the order they were added.
+    pub fn duals(&self) -> &[f64] {
+        self.duals.as_ref()
+    }
+
+    pub fn pivots(&self) -> usize {
+        self.pivots
+    }
+}
+
+impl LinearProgram {
+    pub fn maximize(objective: Vec<f64>) -> Self {
+        Self {
+            objective,
+            constraints: vec![],
+            sense: 1.0,
+        }
+    }
+
+    pub fn minimize(objective: Vec<f64>) -> Self {
+        Self {
+            objective: objective.into_iter().map(|c| -c).collect(),
+            constraints: vec![],
+            sense: -1.0,
+        }
+    }
+
+    pub fn constraint(mut self, coefficients: Vec<f64>, relation: Relation, rhs: f64) -> Self {
+        assert_eq!(coefficients.len(), self.objective.len());
+        self.constraints.push((coefficients, relation, rhs));
+        self
+    }
+
+    pub fn solve(&self) -> Result<LpSolution, LpError> {
+        let mut solution = Tableau::new(self).solve()?;
+        solution.objective *= self.sense;
+        for dual in solution.duals.iter_mut() {
+            *dual *= self.sense;
+        }
+        Ok(solution)
+    }
+}
+
+// Columns are laid out as: the variables, one identity column per row (a slack for `<=`
+// rows, an artificial variable otherwise), then a surplus column for each `>=` row.
+struct Tableau {
+    rows: Vec<Vec<f64>>,
+    objective_row: Vec<f64>,
+    basis: Vec<usize>,
+    costs: Vec<f64>,
+    artificial: Vec<bool>,
+    signs: Vec<f64>,
+    variables: usize,
+    pivots: usize,
+}
+
+impl Tableau {
+    fn new(lp: &LinearProgram) -> Self {
+        let n = lp.objective.len();
+        let m = lp.constraints.len();
+        let surplus = lp
+            .constraints
+            .iter()
+            .filter(|(_, relation, rhs)| {
+                let relation = if *rhs < 0.0 {
+                    relation.flipped()
+                } else {
+                    *relation
+                };
+                relation == Relation::GreaterEqual
+            })
+            .count();
+        let width = n + m + surplus;
+
+        let mut rows = Vec::with_capacity(m);
+        let mut artificial = vec![false; width];
+        let mut signs = Vec::with_capacity(m);
+        let mut next_surplus = n + m;
+        for (i, (coefficients, relation, rhs)) in lp.constraints.iter().enumerate() {
+            // Keep every right hand side non-negative, so the identity columns start feasible.
+            let sign = if *rhs < 0.0 { -1.0 } else { 1.0 };
+            let relation = if sign < 0.0 {
+                relation.flipped()
+            } else {
+                *relation
+            };
+
+            let mut row = vec![0.0; width + 1];
+            for (cell, c) in row.iter_mut().zip(coefficients) {
+                *cell = sign * c;
+            }
+            row[n + i] = 1.0;
+            row[width] = sign * rhs;
+            match relation {
+                Relation::LessEqual => {}
+                Relation::Equal => artificial[n + i] = true,
+                Relation::GreaterEqual => {
+                    artificial[n + i] = true;
+                    row[next_surplus] = -1.0;
+                    next_surplus += 1;
+                }
+            }
+            rows.push(row);
+            signs.push(sign);
+        }
+
+        let mut costs = vec![0.0; width];
+        costs[..n].copy_from_slice(&lp.objective);
+
+        Self {
+            rows,
+            objective_row: vec![],
+            basis: (n..n + m).collect(),
+            costs,
+            artificial,
+            signs,
+            variables: n,
+            pivots: 0,
+        }
+    }
+
+    fn width(&self) -> usize {
+        self.artificial.len()
+    }
+
+    // Reduced costs (z_j - c_j) of every column for the given costs and the current basis.
+    fn price(&mut self, costs: &[f64]) {
+        let width = self.width();
+        let mut objective_row = costs.iter().map(|c| -c).collect::<Vec<_>>();
+        objective_row.push(0.0);
+        for (row, &basic) in self.rows.iter().zip(self.basis.iter()) {
+            let cost = costs[basic];
+            if cost != 0.0 {
+                for (cell, value) in objective_row.iter_mut().zip(row.iter()).take(width + 1) {
+                    *cell += cost * value;
+                }
+            }
+        }
+        self.objective_row = objective_row;
+    }
+
+    fn pivot(&mut self, row: usize, column: usize) {
+        let pivot = self.rows[row][column];
+        for value in self.rows[row].iter_mut() {
+            *value /= pivot;
+        }
+        let pivot_row = self.rows[row].clone();
+        for (i, other) in self.rows.iter_mut().enumerate() {
+            let factor = other[column];
+            if i != row && factor != 0.0 {
+                for (value, p) in other.iter_mut().zip(pivot_row.iter()) {
+                    *value -= factor * p;
+                }
+            }
+        }
+        let factor = self.objective_row[column];
+        if factor != 0.0 {
+            for (value, p) in self.objective_row.iter_mut().zip(pivot_row.iter()) {
+                *value -= factor * p;
+            }
+        }
+        self.basis[row] = column;
+        self.pivots += 1;
+    }
+
+    // Runs simplex pivots until no allowed column improves the objective.
+    fn optimize(&mut self, allowed: &[bool]) -> Result<(), LpError> {
+        let width = self.width();
+        let mut degenerate = 0;
+        loop {
+            let candidates = (0..width).filter(|&j| allowed[j] && self.objective_row[j] < -EPSILON);
+            let entering = if degenerate < DEGENERATE_PIVOTS {
+                candidates.min_by(|&a, &b| self.objective_row[a].total_cmp(&self.objective_row[b]))
+            } else {
+                candidates.min()
+            };
+            let Some(column) = entering else {
+                return Ok(());
+            };
+
+            let leaving = self
+                .rows
+                .iter()
+                .enumerate()
+                .filter(|(_, row)| row[column] > EPSILON)
+                .map(|(i, row)| (row[width] / row[column], self.basis[i], i))
+                .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            let Some((ratio, _, row)) = leaving else {
+                return Err(LpError::Unbounded);
+            };
+
+            degenerate = if ratio <= EPSILON { degenerate + 1 } else { 0 };
+            self.pivot(row, column);
+        }
+    }
+
+    fn solve(mut self) -> Result<LpSolution, LpError> {
+        let width = self.width();
+
+        // Phase 1: push the artificial variables out of the basis.
+        if self.artificial.iter().any(|&a| a) {
+            let costs = self
+                .artificial
+                .iter()
+                .map(|&a| if a { -1.0 } else { 0.0 })
+                .collect::<Vec<_>>();
+            self.price(&costs);
+            self.optimize(&vec![true; width])?;
+            if self.objective_row[width] < -EPSILON * (1.0 + self.rows.len() as f64) {
+                return Err(LpError::Infeasible);
+            }
+
+            for row in 0..self.rows.len() {
+                if !self.artificial[self.basis[row]] {
+                    continue;
+                }
+                let column =
+                    (0..width).find(|&j| !self.artificial[j] && self.rows[row][j].abs() > EPSILON);
+                // Without such a column the row is redundant and the artificial stays at 0.
+                if let Some(column) = column {
+                    self.pivot(row, column);
+                }
+            }
+        }
+
+        // Phase 2: the real objective, never letting an artificial variable back in.
+        let costs = self.costs.clone();
+        self.price(&costs);
+        let allowed = self.artificial.iter().map(|a| !a).collect::<Vec<_>>();
+        self.optimize(&allowed)?;
+
+        let mut variables = vec![0.0; self.variables];
+        for (row, &basic) in self.rows.iter().zip(self.basis.iter()) {
+            if basic < self.variables {
+                variables[basic] = row[width];
+            }
+        }
+        let duals = self
+            .signs
+            .iter()
+            .enumerate()
+            .map(|(i, sign)| sign * self.objective_row[self.variables + i])
+            .collect();
+
+        Ok(LpSolution {
+            variables,
+            objective: self.objective_row[width],
+            duals,
+            pivots: self.pivots,
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use crate::model::{self, ActionBox, IActionBuilder, State};
 
 use std::{
-    collections::HashMap,
+    collections::{hash_map::Entry, HashMap},
     fmt::{Debug, Display},
 };
 
@@ -10,22 +10,87 @@ pub struct Transition {
     from: usize,
     to: usize,
     reward: f64,
+    // The rewards of every objective, only stored when they aren't just `[reward]`.
+    rewards: Option<Box<[f64]>>,
+    // Only stored when the outcome reported costs.
+    costs: Option<Box<[(&'static str, f64)]>>,
     probability: f64,
     action: ActionBox,
 }
 
 impl Transition {
+    pub(crate) fn new(
+        from: usize,
+        to: usize,
+        reward: f64,
+        probability: f64,
+        action: ActionBox,
+    ) -> Self {
+        Self {
+            from,
+            to,
+            reward,
+            rewards: None,
+            costs: None,
+            probability,
+            action,
+        }
+    }
+
+    // Keeps the rewards of every objective next to the scalar reward.
+    pub(crate) fn with_rewards(mut self, rewards: Option<Box<[f64]>>) -> Self {
+        self.rewards = rewards;
+        self
+    }
+
+    pub(crate) fn with_costs(mut self, costs: Option<Box<[(&'static str, f64)]>>) -> Self {
+        self.costs = costs;
+        self
+    }
+
+    // The same transition with another reward to maximise, like a weighted sum of the
+    // objectives. `rewards` stays the same.
+    pub(crate) fn with_reward(&self, reward: f64) -> Self {
+        Self {
+            reward,
+            rewards: Some(self.rewards().into()),
+            ..self.clone()
+        }
+    }
+
     pub fn probability(&self) -> f64 {
         self.probability
     }
 
+    /// The reward solvers maximise: the sum of `rewards`, unless the `Mdp` was scalarised.
     pub fn reward(&self) -> f64 {
         self.reward
     }
 
+    /// The reward for every objective.
+    pub fn rewards(&self) -> &[f64] {
+        self.rewards
+            .as_deref()
+            .unwrap_or(std::slice::from_ref(&self.reward))
+    }
+
+    /// The named costs the outcome reported, like `ActionResult::costs`. See
+    /// `constrained::ConstrainedMdp`.
+    pub fn costs(&self) -> &[(&'static str, f64)] {
+        self.costs.as_deref().unwrap_or_default()
+    }
+
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
     pub fn to(&self) -> usize {
         self.to
     }
+
+    pub fn action(&self) -> &ActionBox {
+        &self.action
+    }
 }
 
 impl Display for Transition {
@@ -37,7 +102,11 @@ impl Display for Transition {
             self.from,
             self.to,
             self.reward
-        )
+        )?;
+        if self.rewards().len() > 1 {
+            write!(f, " {:?}", self.rewards())?;
+        }
+        Ok(())
     }
 }
 
@@ -49,10 +118,31 @@ pub struct Mdp<S: State> {
 
 impl<S: State> Mdp<S> {
     pub fn new(initial: S, actions: Vec<model::Action<S>>) -> Self {
-        let mut stack = vec![initial.get_hash()];
+        Self::explore(vec![initial], actions)
+    }
+
+    // Explores everything reachable from any of the given states. The first one is state 0,
+    // and the others follow in order (unless they repeat).
+    pub(crate) fn explore(initial: Vec<S>, actions: Vec<model::Action<S>>) -> Self {
+        assert!(
+            actions
+                .iter()
+                .all(|a| a.objectives() == actions[0].objectives()),
+            "every action needs the same number of objectives"
+        );
+        let mut stack = vec![];
         let mut hashmap: HashMap<u64, usize> = HashMap::new();
-        hashmap.insert(initial.get_hash(), 0);
-        let mut states = vec![initial];
+        let mut states = vec![];
+        for state in initial {
+            let hash = state.get_hash();
+            if let Entry::Vacant(entry) = hashmap.entry(hash) {
+                entry.insert(states.len());
+                states.push(state);
+                stack.push(hash);
+            }
+        }
+        // Explore from the initial state first, like a single root would.
+        stack.reverse();
 
         let mut transitions = vec![];
 
@@ -61,7 +151,7 @@ impl<S: State> Mdp<S> {
             for action in actions.iter() {
                 let state = &states[from_index];
                 if action.preconditions_valid(state) {
-                    for action_result in action.get_successor_states(&state) {
+                    for action_result in action.get_successor_states(state) {
                         let resulting_state_hash = action_result.state.get_hash();
                         let (index, exists) = match hashmap.get(&resulting_state_hash) {
                             Some(index) => (*index, true),
@@ -79,6 +169,8 @@ impl<S: State> Mdp<S> {
                             action: action.action(),
                             probability: action_result.probability,
                             reward: action_result.reward,
+                            rewards: action_result.rewards,
+                            costs: action_result.costs,
                         };
                         transitions.push(transition);
                     }
@@ -94,7 +186,7 @@ impl<S: State> Mdp<S> {
                     .iter()
                     .filter_map(|t| if t.from == i { Some(t.clone()) } else { None })
                     .fold(HashMap::new(), |mut map, t| {
-                        let exists = map.get(&t.action).is_some();
+                        let exists = map.contains_key(&t.action);
                         let vec = if exists {
                             map.get_mut(&t.action).unwrap()
                         } else {
@@ -114,6 +206,17 @@ impl<S: State> Mdp<S> {
         }
     }
 
+    // For models that don't come from grounded actions, like the ones learning agents learn.
+    pub(crate) fn from_transitions(
+        states: Vec<S>,
+        actions_from_states: Vec<HashMap<ActionBox, Vec<Transition>>>,
+    ) -> Self {
+        Self {
+            states,
+            actions_from_states,
+        }
+    }
+
     pub fn index_of_state(&self, state: &S) -> Option<usize> {
         self.states.iter().position(|s| s == state)
     }
@@ -122,9 +225,84 @@ impl<S: State> Mdp<S> {
         self.states.as_ref()
     }
 
+    /// How many rewards every transition has. Solvers maximise `Transition::reward`, which is
+    /// the plain sum of the objectives, so pick a weighting with `scalarised` before solving a
+    /// model with more than one.
+    pub fn objectives(&self) -> usize {
+        self.actions_from_states
+            .iter()
+            .flat_map(|a| a.values().flatten())
+            .next()
+            .map_or(1, |t| t.rewards().len())
+    }
+
+    /// The same model with the reward of every transition set to the weighted sum of the
+    /// rewards of its objectives, so any solver can find the best policy for a trade-off.
+    pub fn scalarised(&self, weights: &[f64]) -> Mdp<S> {
+        assert_eq!(weights.len(), self.objectives());
+        let actions_from_states = self
+            .actions_from_states
+            .iter()
+            .map(|actions| {
+                actions
+                    .iter()
+                    .map(|(action, transitions)| {
+                        let transitions = transitions
+                            .iter()
+                            .map(|t| {
+                                t.with_reward(
+                                    t.rewards().iter().zip(weights).map(|(r, w)| r * w).sum(),
+                                )
+                            })
+                            .collect();
+                        (action.clone(), transitions)
+                    })
+                    .collect()
+            })
+            .collect();
+        Self::from_transitions(self.states.clone(), actions_from_states)
+    }
+
     pub fn actions(&self, state: usize) -> &HashMap<ActionBox, Vec<Transition>> {
         &self.actions_from_states[state]
     }
+
+    // The actions of a state in a fixed order, so ties between them always go the same way.
+    pub(crate) fn sorted_actions(&self, state: usize) -> Vec<(&ActionBox, &Vec<Transition>)> {
+        let mut actions = self.actions_from_states[state].iter().collect::<Vec<_>>();
+        actions.sort_by_key(|(action, _)| action.sort_key());
+        actions
+    }
+
+    /// The states reachable in one step from each state, under any action.
+    pub fn successors(&self) -> Vec<Vec<usize>> {
+        self.actions_from_states
+            .iter()
+            .map(|actions| {
+                let mut next = actions.values().flatten().map(|t| t.to).collect::<Vec<_>>();
+                next.sort_unstable();
+                next.dedup();
+                next
+            })
+            .collect()
+    }
+
+    /// The states that can reach each state in one step, under any action.
+    pub fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![vec![]; self.states.len()];
+        for t in self
+            .actions_from_states
+            .iter()
+            .flat_map(|a| a.values().flatten())
+        {
+            predecessors[t.to].push(t.from);
+        }
+        for previous in predecessors.iter_mut() {
+            previous.sort_unstable();
+            previous.dedup();
+        }
+        predecessors
+    }
 }
 
 impl<S: State + Debug> Mdp<S> {
@@ -163,14 +341,22 @@ impl<S: State> MdpBuilder<S> {
         self
     }
 
-    pub fn build(self) -> Mdp<S> {
-        let actions = self
-            .actions
+    pub fn initial_state(&self) -> &S {
+        &self.initial_state
+    }
+
+    /// The grounded actions, without exploring any states. Useful for solvers that expand
+    /// the model lazily instead of building the whole `Mdp`.
+    pub fn actions(&self) -> Vec<model::Action<S>> {
+        self.actions
             .iter()
             .enumerate()
-            .map(|(i, a)| a.build(i))
-            .flatten()
-            .collect();
+            .flat_map(|(i, a)| a.build(i))
+            .collect()
+    }
+
+    pub fn build(self) -> Mdp<S> {
+        let actions = self.actions();
         Mdp::new(self.initial_state, actions)
     }
 }
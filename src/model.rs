@@ -1,6 +1,8 @@
+use crate::rng::Rng;
+
 use std::{
     collections::hash_map::DefaultHasher,
-    fmt::Debug,
+    fmt::{Debug, Display},
     hash::{Hash, Hasher},
     marker::PhantomData,
     rc::Rc,
@@ -18,28 +20,113 @@ impl<T: Clone + Eq + Hash> State for T {}
 
 type PreconditionFn<S> = dyn Fn(&S) -> bool;
 type OutcomeFn<S> = dyn Fn(&mut S, &mut f64) -> f64;
+type VectorOutcomeFn<S> = dyn Fn(&mut S, &mut [f64]) -> f64;
+pub(crate) type CostingOutcomeFn<S> =
+    dyn Fn(&mut S, &mut f64, &mut Vec<(&'static str, f64)>) -> f64;
 type ActionBasedPreconditionFn<S, A> = dyn Fn(Rc<A>) -> Rc<dyn Fn(&S) -> bool>;
 type ActionBasedOutcomeFn<S, A> = dyn Fn(Rc<A>) -> Rc<dyn Fn(&mut S, &mut f64) -> f64>;
 
-#[derive(Clone, Hash)]
+#[derive(Clone)]
 pub struct ActionBox {
     id: usize,
     action: Rc<dyn ActionType>,
 }
 
+#[derive(Clone)]
 pub struct Action<S: State> {
     action: ActionBox,
     preconditions: Vec<Rc<PreconditionFn<S>>>,
-    outcomes: Vec<Rc<OutcomeFn<S>>>,
+    outcomes: Vec<Outcome<S>>,
+    objectives: usize,
+}
+
+enum Outcome<S> {
+    Scalar(Rc<OutcomeFn<S>>),
+    Vector(Rc<VectorOutcomeFn<S>>),
+    Costing(Rc<CostingOutcomeFn<S>>),
+}
+
+impl<S> Clone for Outcome<S> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Scalar(effect) => Self::Scalar(effect.clone()),
+            Self::Vector(effect) => Self::Vector(effect.clone()),
+            Self::Costing(effect) => Self::Costing(effect.clone()),
+        }
+    }
 }
 
 pub struct ActionResult<S: State> {
     pub(crate) state: S,
     pub(crate) probability: f64,
     pub(crate) reward: f64,
+    // Only for actions with several objectives, single objective ones just have `reward`.
+    pub(crate) rewards: Option<Box<[f64]>>,
+    // Only for outcomes that reported costs.
+    pub(crate) costs: Option<Box<[(&'static str, f64)]>>,
+}
+
+impl<S: State> ActionResult<S> {
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn probability(&self) -> f64 {
+        self.probability
+    }
+
+    /// The sum of `rewards`.
+    pub fn reward(&self) -> f64 {
+        self.reward
+    }
+
+    /// The reward for every objective, just the `reward` for single objective actions.
+    pub fn rewards(&self) -> &[f64] {
+        self.rewards
+            .as_deref()
+            .unwrap_or(std::slice::from_ref(&self.reward))
+    }
+
+    /// The costs the outcome reported, as `(name, amount)` pairs.
+    pub fn costs(&self) -> &[(&'static str, f64)] {
+        self.costs.as_deref().unwrap_or_default()
+    }
 }
 
 impl<S: State> Action<S> {
+    pub(crate) fn new(
+        action: ActionBox,
+        preconditions: Vec<Rc<PreconditionFn<S>>>,
+        outcomes: Vec<Rc<OutcomeFn<S>>>,
+    ) -> Self {
+        Self {
+            action,
+            preconditions,
+            outcomes: outcomes.into_iter().map(Outcome::Scalar).collect(),
+            objectives: 1,
+        }
+    }
+
+    // Like `new`, for outcomes that also report costs.
+    pub(crate) fn with_costs(
+        action: ActionBox,
+        preconditions: Vec<Rc<PreconditionFn<S>>>,
+        outcomes: Vec<Rc<CostingOutcomeFn<S>>>,
+    ) -> Self {
+        Self {
+            action,
+            preconditions,
+            outcomes: outcomes.into_iter().map(Outcome::Costing).collect(),
+            objectives: 1,
+        }
+    }
+
+    // Restricts where an already grounded action is available.
+    pub(crate) fn with_precondition(mut self, valid: Rc<PreconditionFn<S>>) -> Self {
+        self.preconditions.push(valid);
+        self
+    }
+
     pub fn preconditions_valid(&self, state: &S) -> bool {
         self.preconditions.iter().all(|check| check(state))
     }
@@ -49,20 +136,77 @@ impl<S: State> Action<S> {
             .iter()
             .map(|transition| {
                 let mut next_state = state.clone();
-                let mut reward = 0.0;
-                let chance = transition(&mut next_state, &mut reward);
-                ActionResult {
-                    state: next_state,
-                    probability: chance,
-                    reward,
+                match transition {
+                    Outcome::Scalar(effect) => {
+                        let mut reward = 0.0;
+                        let chance = effect(&mut next_state, &mut reward);
+                        ActionResult {
+                            state: next_state,
+                            probability: chance,
+                            reward,
+                            rewards: None,
+                            costs: None,
+                        }
+                    }
+                    Outcome::Costing(effect) => {
+                        let mut reward = 0.0;
+                        let mut costs = vec![];
+                        let chance = effect(&mut next_state, &mut reward, &mut costs);
+                        ActionResult {
+                            state: next_state,
+                            probability: chance,
+                            reward,
+                            rewards: None,
+                            costs: (!costs.is_empty()).then(|| costs.into_boxed_slice()),
+                        }
+                    }
+                    Outcome::Vector(effect) => {
+                        let mut rewards = vec![0.0; self.objectives];
+                        let chance = effect(&mut next_state, &mut rewards);
+                        ActionResult {
+                            state: next_state,
+                            probability: chance,
+                            reward: rewards.iter().sum(),
+                            rewards: Some(rewards.into_boxed_slice()),
+                            costs: None,
+                        }
+                    }
                 }
             })
             .collect()
     }
 
+    /// Draws a single outcome according to the outcome probabilities, like a simulator would.
+    /// `None` if the action has no outcomes.
+    pub fn sample(&self, state: &S, rng: &mut Rng) -> Option<ActionResult<S>> {
+        if self.outcomes.is_empty() {
+            return None;
+        }
+        let mut outcomes = self.get_successor_states(state);
+        let total = outcomes.iter().map(|o| o.probability).sum::<f64>();
+        let mut remaining = rng.next_f64() * total;
+        let index = outcomes
+            .iter()
+            .position(|o| {
+                remaining -= o.probability;
+                remaining < 0.0
+            })
+            .unwrap_or(outcomes.len() - 1);
+        Some(outcomes.swap_remove(index))
+    }
+
+    pub fn has_outcomes(&self) -> bool {
+        !self.outcomes.is_empty()
+    }
+
     pub fn action(&self) -> ActionBox {
         self.action.clone()
     }
+
+    /// How many rewards every outcome gives, 1 unless built by a `VectorActionBuilder`.
+    pub fn objectives(&self) -> usize {
+        self.objectives
+    }
 }
 
 pub struct SingleActionBuilder<S: State, A: ActionType> {
@@ -92,14 +236,50 @@ impl<S: State, A: ActionType + 'static> SingleActionBuilder<S, A> {
         self
     }
 
+    pub fn build(&self, action_index: usize) -> Action<S> {
+        Action::new(
+            ActionBox::new(action_index, self.action.clone()),
+            self.preconditions.clone(),
+            self.outcomes.clone(),
+        )
+    }
+}
+
+/// Like `SingleActionBuilder`, but every outcome gives a reward for each of several
+/// objectives, e.g. treasure and time. The rewards start at 0.
+pub struct VectorActionBuilder<S: State, A: ActionType> {
+    preconditions: Vec<Rc<PreconditionFn<S>>>,
+    outcomes: Vec<Rc<VectorOutcomeFn<S>>>,
+    action: Rc<A>,
+    objectives: usize,
+}
+
+impl<S: State, A: ActionType + 'static> VectorActionBuilder<S, A> {
+    pub fn new(action: A, objectives: usize) -> Self {
+        Self {
+            preconditions: vec![],
+            outcomes: vec![],
+            action: Rc::new(action),
+            objectives,
+        }
+    }
+
+    pub fn precondition(mut self, valid: Rc<PreconditionFn<S>>) -> Self {
+        self.preconditions.push(valid);
+        self
+    }
+
+    pub fn outcome(mut self, effect: Rc<VectorOutcomeFn<S>>) -> Self {
+        self.outcomes.push(effect);
+        self
+    }
+
     pub fn build(&self, action_index: usize) -> Action<S> {
         Action {
-            action: ActionBox {
-                id: action_index,
-                action: self.action.clone(),
-            },
+            action: ActionBox::new(action_index, self.action.clone()),
             preconditions: self.preconditions.clone(),
-            outcomes: self.outcomes.clone(),
+            outcomes: self.outcomes.iter().cloned().map(Outcome::Vector).collect(),
+            objectives: self.objectives,
         }
     }
 }
@@ -110,6 +290,12 @@ pub struct GroundingActionBuilder<S: State, A: ActionType> {
     action_type: PhantomData<A>,
 }
 
+impl<S: State, A: ActionType + GrounableAction + 'static> Default for GroundingActionBuilder<S, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<S: State, A: ActionType + GrounableAction + 'static> GroundingActionBuilder<S, A> {
     pub fn new() -> Self {
         Self {
@@ -136,14 +322,7 @@ impl<S: State, A: ActionType + GrounableAction + 'static> GroundingActionBuilder
                 let a = Rc::new(action);
                 let preconditions = self.preconditions.iter().map(|p| p(a.clone())).collect();
                 let outcomes = self.outcomes.iter().map(|p| p(a.clone())).collect();
-                return Action {
-                    action: ActionBox {
-                        id: action_index,
-                        action: a,
-                    },
-                    preconditions,
-                    outcomes,
-                };
+                Action::new(ActionBox::new(action_index, a), preconditions, outcomes)
             })
             .collect()
     }
@@ -166,6 +345,17 @@ impl Hash for dyn ActionType {
     }
 }
 
+impl ActionBox {
+    pub(crate) fn new(id: usize, action: Rc<dyn ActionType>) -> Self {
+        Self { id, action }
+    }
+
+    // A stable order for actions, as `HashMap` iteration order changes between runs.
+    pub(crate) fn sort_key(&self) -> (usize, u64) {
+        (self.id, ActionType::hash(&self.action))
+    }
+}
+
 impl PartialEq for ActionBox {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id && ActionType::hash(&self.action) == ActionType::hash(&other.action)
@@ -174,9 +364,16 @@ impl PartialEq for ActionBox {
 
 impl Eq for ActionBox {}
 
-impl ActionBox {
-    pub fn to_string(&self) -> String {
-        self.action.to_string()
+impl Hash for ActionBox {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Hash::hash(&self.id, state);
+        Hash::hash(&self.action, state);
+    }
+}
+
+impl Display for ActionBox {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.action.to_string())
     }
 }
 
@@ -211,10 +408,16 @@ pub trait GrounableAction: Sized {
     fn enumerate() -> Vec<Self>;
 }
 
+impl<S: State, A: ActionType + 'static> IActionBuilder<S> for VectorActionBuilder<S, A> {
+    fn build(&self, action_index: usize) -> Vec<Action<S>> {
+        vec![self.build(action_index)]
+    }
+}
+
 impl<S: State, A: ActionType + 'static + GrounableAction> IActionBuilder<S>
     for GroundingActionBuilder<S, A>
 {
     fn build(&self, action_index: usize) -> Vec<Action<S>> {
-        GroundingActionBuilder::build(&self, action_index)
+        GroundingActionBuilder::build(self, action_index)
     }
 }
@@ -0,0 +1,436 @@
+use crate::{
+    linalg::solve_dense,
+    mdp::Mdp,
+    model::{ActionBox, State},
+    policy::Policy,
+    solver::{
+        evaluate_policy, PolicyIterationSolver, Solver, SolverConfig, SolverError,
+        ValueIterationSolver,
+    },
+};
+
+// Value vectors closer than this count as the same.
+const TOLERANCE: f64 = 1e-9;
+
+/// The expected discounted rewards of a deterministic policy for every objective, from every
+/// state. Indexed by objective, then state.
+pub fn evaluate_objectives<S: State>(
+    mdp: &Mdp<S>,
+    policy: &Policy,
+    discount: f64,
+) -> Vec<Vec<f64>> {
+    (0..mdp.objectives())
+        .map(|k| {
+            let mut weights = vec![0.0; mdp.objectives()];
+            weights[k] = 1.0;
+            evaluate_policy(&mdp.scalarised(&weights), policy.actions(), discount)
+        })
+        .collect()
+}
+
+/// A policy on a trade-off between the objectives, with its value vector in the initial state.
+#[derive(Debug, Clone)]
+pub struct ParetoPolicy {
+    policy: Policy,
+    value: Vec<f64>,
+}
+
+impl ParetoPolicy {
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// The expected discounted reward of every objective, from the initial state.
+    pub fn value(&self) -> &[f64] {
+        self.value.as_ref()
+    }
+
+    /// The scalarised value under some weights.
+    pub fn weighted_value(&self, weights: &[f64]) -> f64 {
+        self.value.iter().zip(weights).map(|(v, w)| v * w).sum()
+    }
+
+    // Whether this policy is at least as good in every objective, and better in one.
+    fn dominates(&self, other: &ParetoPolicy) -> bool {
+        self.value
+            .iter()
+            .zip(other.value.iter())
+            .all(|(a, b)| *a >= b - TOLERANCE)
+            && self
+                .value
+                .iter()
+                .zip(other.value.iter())
+                .any(|(a, b)| *a > b + TOLERANCE)
+    }
+
+    fn same_value(&self, other: &ParetoPolicy) -> bool {
+        self.value
+            .iter()
+            .zip(other.value.iter())
+            .all(|(a, b)| (a - b).abs() <= TOLERANCE)
+    }
+}
+
+fn pareto_policy<S: State>(mdp: &Mdp<S>, policy: Policy, discount: f64) -> ParetoPolicy {
+    let value = evaluate_objectives(mdp, &policy, discount)
+        .into_iter()
+        .map(|values| values[0])
+        .collect();
+    ParetoPolicy { policy, value }
+}
+
+// Orders a coverage set or front by the first objective, then the next ones.
+fn sorted(mut policies: Vec<ParetoPolicy>) -> Vec<ParetoPolicy> {
+    policies.sort_by(|a, b| a.value.partial_cmp(&b.value).unwrap());
+    policies
+}
+
+/// Optimistic linear support (Roijers et al.): finds the convex coverage set, the deterministic
+/// stationary policies that are optimal for some weighting of the objectives.
+///
+/// It starts from the weights that only care about one objective. Every new value vector splits
+/// the weight simplex into the regions where each known policy is best, and the corners of
+/// those regions are the only weights where an unknown policy could do better. Each corner is
+/// solved with `ValueIterationSolver` on the scalarised `Mdp` until none of them improves.
+pub struct ConvexCoverageSetSolver<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    config: SolverConfig,
+    weights: Vec<Vec<f64>>,
+}
+
+impl<'a, S: State> ConvexCoverageSetSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        Self {
+            mdp,
+            discount,
+            config: SolverConfig::default(),
+            weights: vec![],
+        }
+    }
+
+    /// Passed on to the `ValueIterationSolver` of every weighting.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Every weighting that was solved during the last `solve`, in order.
+    pub fn weights(&self) -> &[Vec<f64>] {
+        self.weights.as_ref()
+    }
+
+    /// Solves the `Mdp` scalarised with the given weights, and evaluates the policy for every
+    /// objective.
+    pub fn solve_weights(&self, weights: &[f64]) -> Result<ParetoPolicy, SolverError> {
+        let solution = ValueIterationSolver::new(&self.mdp.scalarised(weights), self.discount)
+            .with_config(self.config.clone())
+            .solve()?;
+        Ok(pareto_policy(
+            self.mdp,
+            solution.policy().clone(),
+            self.discount,
+        ))
+    }
+
+    /// The policies of the convex coverage set, ordered by their values.
+    pub fn solve(&mut self) -> Result<Vec<ParetoPolicy>, SolverError> {
+        let d = self.mdp.objectives();
+        let mut coverage_set: Vec<ParetoPolicy> = vec![];
+        self.weights = vec![];
+        let mut queue = (0..d)
+            .map(|k| {
+                let mut weights = vec![0.0; d];
+                weights[k] = 1.0;
+                weights
+            })
+            .collect::<Vec<_>>();
+
+        while let Some(weights) = queue.pop() {
+            let candidate = self.solve_weights(&weights)?;
+            self.weights.push(weights.clone());
+            let best = coverage_set
+                .iter()
+                .map(|p| p.weighted_value(&weights))
+                .fold(f64::NEG_INFINITY, f64::max);
+            if candidate.weighted_value(&weights) <= best + TOLERANCE
+                || coverage_set.iter().any(|p| p.same_value(&candidate))
+            {
+                continue;
+            }
+
+            coverage_set.push(candidate);
+            let values = coverage_set
+                .iter()
+                .map(|p| p.value.clone())
+                .collect::<Vec<_>>();
+            queue = corner_weights(&values)
+                .into_iter()
+                .filter(|corner| {
+                    !self
+                        .weights
+                        .iter()
+                        .any(|w| w.iter().zip(corner).all(|(a, b)| (a - b).abs() <= 1e-6))
+                })
+                .collect();
+        }
+
+        // Drop the policies that turned out not to be best for any weights.
+        let corners = corner_weights(
+            &coverage_set
+                .iter()
+                .map(|p| p.value.clone())
+                .collect::<Vec<_>>(),
+        );
+        let optimal = coverage_set
+            .iter()
+            .filter(|p| {
+                corners.iter().any(|w| {
+                    let best = coverage_set
+                        .iter()
+                        .map(|q| q.weighted_value(w))
+                        .fold(f64::NEG_INFINITY, f64::max);
+                    p.weighted_value(w) >= best - TOLERANCE
+                })
+            })
+            .cloned()
+            .collect();
+        Ok(sorted(optimal))
+    }
+}
+
+// The corners of the upper surface `max_i w · values[i]` over the weight simplex: the points
+// where `d - 1` of its facets or the edges of the simplex meet. Found by trying every such
+// combination, which is fine for a handful of objectives.
+fn corner_weights(values: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let d = values[0].len();
+    // Unknowns are the weights and the height of the surface. Every candidate equation is
+    // either `w · values[i] - u = 0` or `w_j = 0`.
+    let equations = values
+        .iter()
+        .map(|v| [v.clone(), vec![-1.0]].concat())
+        .chain((0..d).map(|j| {
+            let mut row = vec![0.0; d + 1];
+            row[j] = 1.0;
+            row
+        }))
+        .collect::<Vec<_>>();
+
+    let mut corners: Vec<Vec<f64>> = vec![];
+    for combination in combinations(equations.len(), d) {
+        // The weights add up to 1.
+        let mut a = vec![[vec![1.0; d], vec![0.0]].concat()];
+        let mut b = vec![1.0];
+        for &e in combination.iter() {
+            a.push(equations[e].clone());
+            b.push(0.0);
+        }
+        let Some(solution) = solve_dense(a, b) else {
+            continue;
+        };
+        let (weights, height) = (&solution[..d], solution[d]);
+        let on_surface = weights.iter().all(|&w| w >= -TOLERANCE)
+            && values
+                .iter()
+                .all(|v| v.iter().zip(weights).map(|(x, w)| x * w).sum::<f64>() <= height + 1e-7);
+        let weights = weights.iter().map(|w| w.max(0.0)).collect::<Vec<_>>();
+        if on_surface
+            && !corners.iter().any(|c| {
+                c.iter()
+                    .zip(weights.iter())
+                    .all(|(a, b)| (a - b).abs() <= 1e-6)
+            })
+        {
+            corners.push(weights);
+        }
+    }
+    corners
+}
+
+// Every way to pick `k` of `0..n`, in increasing order.
+fn combinations(n: usize, k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    (k - 1..n)
+        .flat_map(|last| {
+            combinations(last, k - 1).into_iter().map(move |mut c| {
+                c.push(last);
+                c
+            })
+        })
+        .collect()
+}
+
+/// Branch and bound over deterministic stationary policies: finds every value vector in the
+/// initial state that no other such policy dominates, with a policy for each.
+///
+/// Unlike the convex coverage set, most of these policies are not optimal for any weighting,
+/// so no scalarised solve finds them. The search starts from the convex coverage set and picks
+/// an action for one state the policy reaches at a time. Every partial policy is bounded by its
+/// ideal point, the best value of each objective on its own over the states without an action
+/// yet, and dropped when a known policy is at least as good in every objective. Only the
+/// states a policy reaches get an action.
+///
+/// There can be exponentially many policies, so this is meant for small models.
+pub struct ParetoFrontSolver<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    config: SolverConfig,
+    // The model of every single objective.
+    objectives: Vec<Mdp<S>>,
+    nodes: usize,
+}
+
+impl<'a, S: State> ParetoFrontSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        let objectives = (0..mdp.objectives())
+            .map(|k| {
+                let mut weights = vec![0.0; mdp.objectives()];
+                weights[k] = 1.0;
+                mdp.scalarised(&weights)
+            })
+            .collect();
+        Self {
+            mdp,
+            discount,
+            config: SolverConfig::default(),
+            objectives,
+            nodes: 0,
+        }
+    }
+
+    /// Used by the `ConvexCoverageSetSolver` the search starts from, and for the bounds.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// How many partial policies the last `solve` bounded.
+    pub fn nodes(&self) -> usize {
+        self.nodes
+    }
+
+    /// The non-dominated policies, ordered by their values.
+    pub fn solve(&mut self) -> Result<Vec<ParetoPolicy>, SolverError> {
+        let mut front = ConvexCoverageSetSolver::new(self.mdp, self.discount)
+            .with_config(self.config.clone())
+            .solve()?;
+        let mut ideal = vec![];
+        for model in self.objectives.iter() {
+            let solution = PolicyIterationSolver::new(model, self.discount)
+                .with_config(self.config.clone())
+                .solve()?;
+            ideal.push(solution.values().to_vec());
+        }
+        self.nodes = 0;
+        let mut partial = vec![None; self.mdp.states().len()];
+        self.search(&mut partial, &ideal, &mut front);
+        Ok(sorted(front))
+    }
+
+    // `ideal` holds the best value of every objective on its own, in every state.
+    fn search(
+        &mut self,
+        partial: &mut Vec<Option<ActionBox>>,
+        ideal: &[Vec<f64>],
+        front: &mut Vec<ParetoPolicy>,
+    ) {
+        self.nodes += 1;
+        let (reached, unassigned) = self.reached(partial);
+        let bound = (0..self.objectives.len())
+            .map(|k| self.bound(k, partial, &reached, &ideal[k]))
+            .collect::<Vec<_>>();
+        if front.iter().any(|p| {
+            p.value
+                .iter()
+                .zip(bound.iter())
+                .all(|(a, b)| *a >= b - TOLERANCE)
+        }) {
+            return;
+        }
+
+        let Some(state) = unassigned else {
+            let candidate = pareto_policy(self.mdp, Policy::new(partial.clone()), self.discount);
+            if front
+                .iter()
+                .all(|p| !p.dominates(&candidate) && !p.same_value(&candidate))
+            {
+                front.retain(|p| !candidate.dominates(p));
+                front.push(candidate);
+            }
+            return;
+        };
+
+        let mut actions = self.mdp.actions(state).keys().cloned().collect::<Vec<_>>();
+        actions.sort_by_key(|a| a.sort_key());
+        for action in actions {
+            partial[state] = Some(action);
+            self.search(partial, ideal, front);
+        }
+        partial[state] = None;
+    }
+
+    // An upper bound on one objective in the initial state: the partial policy is followed
+    // exactly, and every state without an action yet is worth its best value on its own.
+    fn bound(
+        &self,
+        objective: usize,
+        partial: &[Option<ActionBox>],
+        reached: &[usize],
+        ideal: &[f64],
+    ) -> f64 {
+        if reached.is_empty() {
+            return ideal[0];
+        }
+        let mdp = &self.objectives[objective];
+        let mut local_index = vec![usize::MAX; partial.len()];
+        for (local, &state) in reached.iter().enumerate() {
+            local_index[state] = local;
+        }
+        let size = reached.len();
+        let mut a = vec![vec![0.0; size]; size];
+        let mut b = vec![0.0; size];
+        for (row, &state) in reached.iter().enumerate() {
+            a[row][row] = 1.0;
+            let action = partial[state].as_ref().unwrap();
+            for t in mdp.actions(state)[action].iter() {
+                b[row] += t.probability() * t.reward();
+                let weight = self.discount * t.probability();
+                match local_index[t.to()] {
+                    usize::MAX => b[row] += weight * ideal[t.to()],
+                    local => a[row][local] -= weight,
+                }
+            }
+        }
+        // A loop that never ends without discounting has no unique value, so it can't be
+        // bounded.
+        solve_dense(a, b).map_or(f64::INFINITY, |values| values[0])
+    }
+
+    // The states with an action that the partial policy reaches from the initial state, with
+    // the initial state first, and the first state without one in depth first order.
+    fn reached(&self, partial: &[Option<ActionBox>]) -> (Vec<usize>, Option<usize>) {
+        let mut visited = vec![false; partial.len()];
+        let mut stack = vec![0];
+        visited[0] = true;
+        let mut reached = vec![];
+        let mut unassigned = None;
+        while let Some(state) = stack.pop() {
+            let Some(action) = &partial[state] else {
+                if unassigned.is_none() && !self.mdp.actions(state).is_empty() {
+                    unassigned = Some(state);
+                }
+                continue;
+            };
+            reached.push(state);
+            for t in self.mdp.actions(state)[action].iter().rev() {
+                if t.probability() > 0.0 && !visited[t.to()] {
+                    visited[t.to()] = true;
+                    stack.push(t.to());
+                }
+            }
+        }
+        (reached, unassigned)
+    }
+}
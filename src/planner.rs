@@ -0,0 +1,315 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    model::{Action, ActionBox, State},
+    rng::Rng,
+};
+
+const DEFAULT_ITERATIONS: usize = 1000;
+const DEFAULT_MAX_DEPTH: usize = 100;
+
+type RolloutFn<'a, S> = dyn Fn(&S, &[ActionBox], &mut Rng) -> usize + 'a;
+
+/// How often an action was tried at the root of the search tree and its mean return.
+#[derive(Debug, Clone)]
+pub struct ActionStatistics {
+    action: ActionBox,
+    visits: usize,
+    value: f64,
+}
+
+impl ActionStatistics {
+    pub fn action(&self) -> &ActionBox {
+        &self.action
+    }
+
+    pub fn visits(&self) -> usize {
+        self.visits
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+}
+
+/// The result of one search. `action` is `None` if nothing can be done in the state.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    action: Option<ActionBox>,
+    statistics: Vec<ActionStatistics>,
+    iterations: usize,
+    elapsed: Duration,
+}
+
+impl Plan {
+    /// The most visited action at the root.
+    pub fn action(&self) -> Option<&ActionBox> {
+        self.action.as_ref()
+    }
+
+    pub fn statistics(&self) -> &[ActionStatistics] {
+        self.statistics.as_ref()
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+struct Edge {
+    // Index into the planner's actions.
+    action: usize,
+    visits: usize,
+    total: f64,
+    children: HashMap<u64, usize>,
+}
+
+struct Node<S: State> {
+    state: S,
+    visits: usize,
+    edges: Vec<Edge>,
+}
+
+/// Monte Carlo tree search with UCB1 action selection (UCT).
+///
+/// An anytime online planner: it never builds an `Mdp`, it only samples outcomes of the
+/// actions (`Action::sample`) from the state it is asked about. Every iteration walks down
+/// the tree picking actions by `mean return + exploration * sqrt(ln N / n)`, adds one new
+/// node, estimates its value with a rollout and backs the return up along the path. Actions
+/// that haven't been tried yet are always picked first.
+///
+/// The exploration constant should be on the same scale as the returns. The search stops
+/// after the iteration limit or the timeout, whichever comes first, and runs 1000 iterations
+/// if neither is set. Every `plan` call starts a fresh tree.
+pub struct UctPlanner<'a, S: State> {
+    actions: Vec<Action<S>>,
+    discount: f64,
+    exploration: f64,
+    rollout: Box<RolloutFn<'a, S>>,
+    max_iterations: Option<usize>,
+    timeout: Option<Duration>,
+    max_depth: usize,
+    rng: Rng,
+    nodes: Vec<Node<S>>,
+}
+
+impl<'a, S: State> UctPlanner<'a, S> {
+    pub fn new(actions: Vec<Action<S>>, discount: f64) -> Self {
+        Self {
+            actions,
+            discount,
+            exploration: std::f64::consts::SQRT_2,
+            rollout: Box::new(|_, actions, rng| rng.below(actions.len())),
+            max_iterations: None,
+            timeout: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+            rng: Rng::new(0),
+            nodes: vec![],
+        }
+    }
+
+    pub fn exploration(mut self, exploration: f64) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    /// Picks the action to take during rollouts, as an index into the available actions.
+    /// Defaults to uniformly random.
+    pub fn rollout_policy(
+        mut self,
+        rollout: impl Fn(&S, &[ActionBox], &mut Rng) -> usize + 'a,
+    ) -> Self {
+        self.rollout = Box::new(rollout);
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// How many steps a simulation looks ahead, counting both the tree and the rollout.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    pub fn actions(&self) -> &[Action<S>] {
+        self.actions.as_ref()
+    }
+
+    pub fn plan(&mut self, state: &S) -> Plan {
+        let start = Instant::now();
+        let max_iterations = match (self.max_iterations, self.timeout) {
+            (None, None) => Some(DEFAULT_ITERATIONS),
+            (max_iterations, _) => max_iterations,
+        };
+
+        self.nodes.clear();
+        self.add_node(state.clone());
+        let mut iterations = 0;
+        while !self.nodes[0].edges.is_empty() {
+            if max_iterations.is_some_and(|max| iterations >= max)
+                || self
+                    .timeout
+                    .is_some_and(|timeout| start.elapsed() >= timeout)
+            {
+                break;
+            }
+            self.simulate(0, 0);
+            iterations += 1;
+        }
+
+        let statistics = self.nodes[0]
+            .edges
+            .iter()
+            .map(|edge| ActionStatistics {
+                action: self.actions[edge.action].action(),
+                visits: edge.visits,
+                value: if edge.visits == 0 {
+                    0.0
+                } else {
+                    edge.total / edge.visits as f64
+                },
+            })
+            .collect::<Vec<_>>();
+        let action = statistics
+            .iter()
+            .reduce(|accum, item| {
+                if (accum.visits, accum.value) >= (item.visits, item.value) {
+                    accum
+                } else {
+                    item
+                }
+            })
+            .map(|s| s.action.clone());
+
+        Plan {
+            action,
+            statistics,
+            iterations,
+            elapsed: start.elapsed(),
+        }
+    }
+
+    // Actions without outcomes can't be simulated, so they are never planned with.
+    fn available(&self, state: &S) -> Vec<usize> {
+        (0..self.actions.len())
+            .filter(|&i| {
+                self.actions[i].has_outcomes() && self.actions[i].preconditions_valid(state)
+            })
+            .collect()
+    }
+
+    fn add_node(&mut self, state: S) -> usize {
+        let edges = self
+            .available(&state)
+            .into_iter()
+            .map(|action| Edge {
+                action,
+                visits: 0,
+                total: 0.0,
+                children: HashMap::new(),
+            })
+            .collect();
+        self.nodes.push(Node {
+            state,
+            visits: 0,
+            edges,
+        });
+        self.nodes.len() - 1
+    }
+
+    fn select(&mut self, node: usize) -> usize {
+        let node = &self.nodes[node];
+        let untried = (0..node.edges.len())
+            .filter(|&i| node.edges[i].visits == 0)
+            .collect::<Vec<_>>();
+        if !untried.is_empty() {
+            return untried[self.rng.below(untried.len())];
+        }
+
+        let log_visits = (node.visits as f64).ln();
+        (0..node.edges.len())
+            .map(|i| {
+                let edge = &node.edges[i];
+                let visits = edge.visits as f64;
+                let ucb = edge.total / visits + self.exploration * (log_visits / visits).sqrt();
+                (ucb, i)
+            })
+            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+            .map(|(_, i)| i)
+            .unwrap()
+    }
+
+    // Returns the discounted return of one simulation from `node`.
+    fn simulate(&mut self, node: usize, depth: usize) -> f64 {
+        if depth >= self.max_depth || self.nodes[node].edges.is_empty() {
+            return 0.0;
+        }
+
+        let edge = self.select(node);
+        let action = self.nodes[node].edges[edge].action;
+        let result = self.actions[action]
+            .sample(&self.nodes[node].state, &mut self.rng)
+            .expect("available actions have outcomes");
+        let hash = result.state.get_hash();
+        let value = match self.nodes[node].edges[edge].children.get(&hash) {
+            Some(&child) => result.reward + self.discount * self.simulate(child, depth + 1),
+            None => {
+                let future = self.rollout(&result.state, depth + 1);
+                let child = self.add_node(result.state);
+                self.nodes[node].edges[edge].children.insert(hash, child);
+                result.reward + self.discount * future
+            }
+        };
+
+        self.nodes[node].visits += 1;
+        let edge = &mut self.nodes[node].edges[edge];
+        edge.visits += 1;
+        edge.total += value;
+        value
+    }
+
+    fn rollout(&mut self, state: &S, mut depth: usize) -> f64 {
+        let mut state = state.clone();
+        let mut value = 0.0;
+        let mut weight = 1.0;
+        while depth < self.max_depth {
+            let available = self.available(&state);
+            if available.is_empty() {
+                break;
+            }
+            let boxes = available
+                .iter()
+                .map(|&i| self.actions[i].action())
+                .collect::<Vec<_>>();
+            let choice = (self.rollout)(&state, &boxes, &mut self.rng);
+            let result = self.actions[available[choice]]
+                .sample(&state, &mut self.rng)
+                .expect("available actions have outcomes");
+            value += weight * result.reward;
+            weight *= self.discount;
+            state = result.state;
+            depth += 1;
+        }
+        value
+    }
+}
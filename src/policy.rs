@@ -3,9 +3,10 @@ use std::fmt::Debug;
 use crate::{
     mdp::Mdp,
     model::{ActionBox, State},
+    rng::Rng,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Policy {
     actions: Vec<Option<ActionBox>>,
 }
@@ -33,3 +34,99 @@ impl Policy {
         }
     }
 }
+
+/// A policy that picks every action of a state with some probability.
+///
+/// Each state holds `(action, probability)` pairs, which should add up to 1. States without
+/// any are terminal. Randomized policies come out of softmax exploration and constrained
+/// problems, and a deterministic `Policy` converts into one with `From`.
+#[derive(Debug, Clone)]
+pub struct StochasticPolicy {
+    distributions: Vec<Vec<(ActionBox, f64)>>,
+}
+
+impl StochasticPolicy {
+    pub fn new(distributions: Vec<Vec<(ActionBox, f64)>>) -> Self {
+        Self { distributions }
+    }
+
+    /// Picks uniformly among the available actions of every state.
+    pub fn uniform<S: State>(mdp: &Mdp<S>) -> Self {
+        let distributions = (0..mdp.states().len())
+            .map(|i| {
+                let mut actions = mdp.actions(i).keys().cloned().collect::<Vec<_>>();
+                actions.sort_by_key(|a| a.sort_key());
+                let n = actions.len() as f64;
+                actions.into_iter().map(|a| (a, 1.0 / n)).collect()
+            })
+            .collect();
+        Self { distributions }
+    }
+
+    pub fn distributions(&self) -> &[Vec<(ActionBox, f64)>] {
+        self.distributions.as_ref()
+    }
+
+    pub fn distribution(&self, state: usize) -> &[(ActionBox, f64)] {
+        self.distributions[state].as_ref()
+    }
+
+    /// The probability of picking an action in a state, 0 if it isn't in the distribution.
+    pub fn probability(&self, state: usize, action: &ActionBox) -> f64 {
+        self.distributions[state]
+            .iter()
+            .filter(|(a, _)| a == action)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    /// Draws an action for a state, or `None` if it has none.
+    pub fn sample(&self, state: usize, rng: &mut Rng) -> Option<&ActionBox> {
+        let distribution = &self.distributions[state];
+        let total = distribution.iter().map(|(_, p)| p).sum::<f64>();
+        let mut remaining = rng.next_f64() * total;
+        distribution
+            .iter()
+            .find(|(_, p)| {
+                remaining -= p;
+                remaining < 0.0
+            })
+            .or(distribution.last())
+            .map(|(a, _)| a)
+    }
+
+    /// The deterministic policy that takes the most likely action of every state. Ties go to
+    /// the action listed first.
+    pub fn greedy(&self) -> Policy {
+        let actions = self
+            .distributions
+            .iter()
+            .map(|distribution| {
+                distribution
+                    .iter()
+                    .map(|(action, p)| (*p, action))
+                    .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                    .map(|(_, action)| action.clone())
+            })
+            .collect();
+        Policy::new(actions)
+    }
+
+    /// Whether every state has at most one action with a nonzero probability.
+    pub fn is_deterministic(&self) -> bool {
+        self.distributions
+            .iter()
+            .all(|d| d.iter().filter(|(_, p)| *p > 0.0).count() <= 1)
+    }
+}
+
+impl From<&Policy> for StochasticPolicy {
+    fn from(policy: &Policy) -> Self {
+        let distributions = policy
+            .actions()
+            .iter()
+            .map(|action| action.iter().map(|a| (a.clone(), 1.0)).collect())
+            .collect();
+        Self { distributions }
+    }
+}
@@ -0,0 +1,605 @@
+use std::{collections::HashMap, hash::Hash, rc::Rc};
+
+use crate::{
+    mdp::Mdp,
+    model::{self, ActionBox, ActionType, IActionBuilder, SingleActionBuilder, State},
+    rng::Rng,
+};
+
+pub trait Observation: Clone + Eq + Hash {}
+
+impl<T: Clone + Eq + Hash> Observation for T {}
+
+type ObservingOutcomeFn<S, O> = dyn Fn(&mut S, &mut f64, &mut Vec<(O, f64)>) -> f64;
+
+mod baselines;
+mod point_based;
+
+pub use baselines::{FibSolver, QmdpSolver};
+pub use point_based::{AlphaVector, PointBasedMethod, PointBasedSolver, ValueFunction};
+
+// Observations with a probability difference below this are the same.
+const OBSERVATION_EPSILON: f64 = 1e-9;
+
+/// A probability distribution over the states of a `Pomdp`, laid out like the values of a
+/// `Solution`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Belief {
+    probabilities: Vec<f64>,
+}
+
+impl Belief {
+    /// The probabilities are normalised, so they only need to be in proportion.
+    pub fn new(probabilities: Vec<f64>) -> Self {
+        let total = probabilities.iter().sum::<f64>();
+        Self {
+            probabilities: probabilities.iter().map(|p| p / total).collect(),
+        }
+    }
+
+    /// Certain to be in the given state.
+    pub fn point(num_states: usize, state: usize) -> Self {
+        let mut probabilities = vec![0.0; num_states];
+        probabilities[state] = 1.0;
+        Self { probabilities }
+    }
+
+    pub fn probabilities(&self) -> &[f64] {
+        self.probabilities.as_ref()
+    }
+
+    pub fn probability(&self, state: usize) -> f64 {
+        self.probabilities[state]
+    }
+
+    /// The states with a nonzero probability.
+    pub fn support(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.probabilities.len()).filter(|&i| self.probabilities[i] > 0.0)
+    }
+
+    /// Draws a state.
+    pub fn sample(&self, rng: &mut Rng) -> usize {
+        let states = self
+            .support()
+            .map(|i| (i, self.probabilities[i]))
+            .collect::<Vec<_>>();
+        sample(&states, rng)
+    }
+
+    pub fn most_likely(&self) -> usize {
+        (0..self.probabilities.len())
+            .map(|i| (self.probabilities[i], i))
+            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+            .map(|(_, i)| i)
+            .unwrap()
+    }
+}
+
+/// A partially observable `Mdp`.
+///
+/// The agent never sees the state itself. After every action it receives an observation,
+/// drawn from a distribution that depends on the action and the state it led to, and it
+/// tracks a `Belief` over where it might be instead. The states of the `Mdp` are the hidden
+/// states, and the initial belief replaces the initial state.
+pub struct Pomdp<S: State, O: Observation> {
+    mdp: Mdp<S>,
+    observations: Vec<O>,
+    // (observation, probability) pairs per next state and action.
+    observation_model: Vec<HashMap<ActionBox, Vec<(usize, f64)>>>,
+    initial_belief: Belief,
+}
+
+impl<S: State, O: Observation> Pomdp<S, O> {
+    /// Adds an observation model to an `Mdp`, which gives the distribution of the
+    /// observations for every action and the state it led to. The initial belief is certain
+    /// to be in state 0.
+    pub fn new(mdp: Mdp<S>, observation: impl Fn(&ActionBox, &S) -> Vec<(O, f64)>) -> Self {
+        let n = mdp.states().len();
+        let mut observations = ObservationIndex::default();
+        let mut observation_model: Vec<HashMap<ActionBox, Vec<(usize, f64)>>> =
+            vec![HashMap::new(); n];
+        for t in (0..n).flat_map(|i| mdp.actions(i).values().flatten()) {
+            if observation_model[t.to()].contains_key(t.action()) {
+                continue;
+            }
+            let distribution = observation(t.action(), &mdp.states()[t.to()])
+                .into_iter()
+                .map(|(o, p)| (observations.index(o), p))
+                .collect();
+            observation_model[t.to()].insert(t.action().clone(), distribution);
+        }
+
+        Self {
+            mdp,
+            observations: observations.observations,
+            observation_model,
+            initial_belief: Belief::point(n, 0),
+        }
+    }
+
+    pub fn with_initial_belief(mut self, initial_belief: Belief) -> Self {
+        self.initial_belief = initial_belief;
+        self
+    }
+
+    /// The underlying fully observable model.
+    pub fn mdp(&self) -> &Mdp<S> {
+        &self.mdp
+    }
+
+    pub fn num_states(&self) -> usize {
+        self.mdp.states().len()
+    }
+
+    /// Every observation that can happen, in the order they were first seen. Observations
+    /// are referred to by their index in here.
+    pub fn observations(&self) -> &[O] {
+        self.observations.as_ref()
+    }
+
+    pub fn index_of_observation(&self, observation: &O) -> Option<usize> {
+        self.observations.iter().position(|o| o == observation)
+    }
+
+    pub fn initial_belief(&self) -> &Belief {
+        &self.initial_belief
+    }
+
+    /// The `(observation, probability)` pairs after taking an action and ending up in
+    /// `next_state`. Empty if the action can't lead there.
+    pub fn observation_distribution(
+        &self,
+        action: &ActionBox,
+        next_state: usize,
+    ) -> &[(usize, f64)] {
+        self.observation_model[next_state]
+            .get(action)
+            .map(|d| d.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn observation_probability(
+        &self,
+        action: &ActionBox,
+        next_state: usize,
+        observation: usize,
+    ) -> f64 {
+        self.observation_distribution(action, next_state)
+            .iter()
+            .filter(|(o, _)| *o == observation)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    /// The actions available in every state the belief considers possible (other than
+    /// terminal ones), in a stable order.
+    pub fn actions(&self, belief: &Belief) -> Vec<ActionBox> {
+        let mut support = belief
+            .support()
+            .filter(|&i| !self.mdp.actions(i).is_empty());
+        let Some(first) = support.next() else {
+            return vec![];
+        };
+        let mut actions = self
+            .mdp
+            .actions(first)
+            .keys()
+            .filter(|a| {
+                belief
+                    .support()
+                    .all(|i| self.mdp.actions(i).is_empty() || self.mdp.actions(i).contains_key(*a))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        actions.sort_by_key(|a| a.sort_key());
+        actions
+    }
+
+    /// Simulates an action in a hidden state. Returns the next state, the observation and
+    /// the reward.
+    pub fn step(&self, state: usize, action: &ActionBox, rng: &mut Rng) -> (usize, usize, f64) {
+        let transitions = &self.mdp.actions(state)[action];
+        let outcomes = transitions
+            .iter()
+            .map(|t| (t, t.probability()))
+            .collect::<Vec<_>>();
+        let t = sample(&outcomes, rng);
+        let observation = sample(self.observation_distribution(action, t.to()), rng);
+        (t.to(), observation, t.reward())
+    }
+
+    /// Whether every state the belief considers possible is terminal.
+    pub fn is_terminal(&self, belief: &Belief) -> bool {
+        belief.support().all(|i| self.mdp.actions(i).is_empty())
+    }
+
+    pub fn expected_reward(&self, belief: &Belief, action: &ActionBox) -> f64 {
+        belief
+            .support()
+            .filter_map(|i| {
+                let transitions = self.mdp.actions(i).get(action)?;
+                let reward = transitions
+                    .iter()
+                    .map(|t| t.probability() * t.reward())
+                    .sum::<f64>();
+                Some(belief.probability(i) * reward)
+            })
+            .sum()
+    }
+
+    /// The distribution over the next states after taking an action, before seeing the
+    /// observation. States where the action isn't available contribute nothing.
+    pub fn predict(&self, belief: &Belief, action: &ActionBox) -> Vec<f64> {
+        let mut next = vec![0.0; self.num_states()];
+        for i in belief.support() {
+            for t in self.mdp.actions(i).get(action).into_iter().flatten() {
+                next[t.to()] += belief.probability(i) * t.probability();
+            }
+        }
+        next
+    }
+
+    /// The probability of seeing an observation after taking an action in a belief.
+    pub fn observation_likelihood(
+        &self,
+        belief: &Belief,
+        action: &ActionBox,
+        observation: usize,
+    ) -> f64 {
+        self.predict(belief, action)
+            .iter()
+            .enumerate()
+            .filter(|(_, &p)| p > 0.0)
+            .map(|(next, p)| p * self.observation_probability(action, next, observation))
+            .sum()
+    }
+
+    /// Bayes' rule: the belief after taking an action and seeing an observation, or `None` if
+    /// the observation is impossible.
+    pub fn update(
+        &self,
+        belief: &Belief,
+        action: &ActionBox,
+        observation: usize,
+    ) -> Option<Belief> {
+        let probabilities = self
+            .predict(belief, action)
+            .iter()
+            .enumerate()
+            .map(|(next, &p)| {
+                if p == 0.0 {
+                    0.0
+                } else {
+                    p * self.observation_probability(action, next, observation)
+                }
+            })
+            .collect::<Vec<_>>();
+        if probabilities.iter().sum::<f64>() <= 0.0 {
+            return None;
+        }
+        Some(Belief::new(probabilities))
+    }
+}
+
+// The model as dense tables for the solvers, with the actions numbered in a stable order.
+// An action that isn't available in a state gives no reward and leads nowhere there.
+pub(crate) struct Tables {
+    actions: Vec<ActionBox>,
+    num_observations: usize,
+    // Expected reward per action and state.
+    rewards: Vec<Vec<f64>>,
+    // (next state, probability) per action and state.
+    transitions: Vec<Vec<Vec<(usize, f64)>>>,
+    // (observation, probability) per action and next state.
+    observations: Vec<Vec<Vec<(usize, f64)>>>,
+}
+
+impl Tables {
+    pub(crate) fn new<S: State, O: Observation>(pomdp: &Pomdp<S, O>) -> Self {
+        let n = pomdp.num_states();
+        let mdp = pomdp.mdp();
+        let mut actions = (0..n)
+            .flat_map(|i| mdp.actions(i).keys().cloned())
+            .collect::<Vec<_>>();
+        actions.sort_by_key(|a| a.sort_key());
+        actions.dedup();
+
+        let rewards = actions
+            .iter()
+            .map(|a| {
+                (0..n)
+                    .map(|i| {
+                        mdp.actions(i)
+                            .get(a)
+                            .map(|ts| ts.iter().map(|t| t.probability() * t.reward()).sum())
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+        let transitions = actions
+            .iter()
+            .map(|a| {
+                (0..n)
+                    .map(|i| {
+                        mdp.actions(i)
+                            .get(a)
+                            .map(|ts| ts.iter().map(|t| (t.to(), t.probability())).collect())
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+        let observations = actions
+            .iter()
+            .map(|a| {
+                (0..n)
+                    .map(|i| pomdp.observation_distribution(a, i).to_vec())
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            actions,
+            num_observations: pomdp.observations().len(),
+            rewards,
+            transitions,
+            observations,
+        }
+    }
+
+    pub(crate) fn num_states(&self) -> usize {
+        self.rewards.first().map(|r| r.len()).unwrap_or_default()
+    }
+
+    pub(crate) fn is_terminal(&self, state: usize) -> bool {
+        (0..self.actions.len()).all(|a| self.transitions[a][state].is_empty())
+    }
+
+    // The index of every action in `actions`, for the actions of a belief.
+    pub(crate) fn indices(&self, actions: &[ActionBox]) -> Vec<usize> {
+        actions
+            .iter()
+            .filter_map(|a| self.actions.iter().position(|b| b == a))
+            .collect()
+    }
+
+    // `sum_s' T(s, a, s') O(o | a, s') alpha(s')` for every state `s`.
+    pub(crate) fn project(&self, action: usize, observation: usize, alpha: &[f64]) -> Vec<f64> {
+        // The observation part only depends on the next state, so weight alpha with it first.
+        let weighted = (0..alpha.len())
+            .map(|next| {
+                self.observations[action][next]
+                    .iter()
+                    .filter(|&&(o, p)| o == observation && p > 0.0)
+                    .map(|(_, p)| p * alpha[next])
+                    .sum::<f64>()
+            })
+            .collect::<Vec<_>>();
+        self.transitions[action]
+            .iter()
+            .map(|ts| {
+                ts.iter()
+                    .filter(|&&(next, p)| p > 0.0 && weighted[next] != 0.0)
+                    .map(|&(next, p)| p * weighted[next])
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+// Draws one of the items with probability in proportion to its weight.
+pub(crate) fn sample<T: Copy>(items: &[(T, f64)], rng: &mut Rng) -> T {
+    let total = items.iter().map(|(_, p)| p).sum::<f64>();
+    let mut remaining = rng.next_f64() * total;
+    items
+        .iter()
+        .find(|(_, p)| {
+            remaining -= p;
+            remaining < 0.0
+        })
+        .or(items.last())
+        .map(|&(item, _)| item)
+        .expect("nothing to sample from")
+}
+
+struct ObservationIndex<O: Observation> {
+    observations: Vec<O>,
+    indices: HashMap<O, usize>,
+}
+
+impl<O: Observation> Default for ObservationIndex<O> {
+    fn default() -> Self {
+        Self {
+            observations: vec![],
+            indices: HashMap::new(),
+        }
+    }
+}
+
+impl<O: Observation> ObservationIndex<O> {
+    fn index(&mut self, observation: O) -> usize {
+        if let Some(&index) = self.indices.get(&observation) {
+            return index;
+        }
+        self.indices
+            .insert(observation.clone(), self.observations.len());
+        self.observations.push(observation);
+        self.observations.len() - 1
+    }
+}
+
+/// Like `SingleActionBuilder`, but every outcome also emits observations.
+///
+/// An outcome pushes the observations it can emit with their probabilities (which should add
+/// up to 1). The observation model is per action and next state, so every outcome of the
+/// action that leads to the same state has to emit the same distribution. It can also be
+/// added to an ordinary `MdpBuilder`, which ignores the observations.
+pub struct ObservingActionBuilder<S: State, A: ActionType, O: Observation> {
+    inner: SingleActionBuilder<S, A>,
+    outcomes: Vec<Rc<ObservingOutcomeFn<S, O>>>,
+}
+
+impl<S: State + 'static, A: ActionType + 'static, O: Observation + 'static>
+    ObservingActionBuilder<S, A, O>
+{
+    pub fn new(action: A) -> Self {
+        Self {
+            inner: SingleActionBuilder::new(action),
+            outcomes: vec![],
+        }
+    }
+
+    pub fn precondition(mut self, valid: Rc<dyn Fn(&S) -> bool>) -> Self {
+        self.inner = self.inner.precondition(valid);
+        self
+    }
+
+    pub fn outcome(mut self, effect: Rc<ObservingOutcomeFn<S, O>>) -> Self {
+        let without_observations = effect.clone();
+        self.inner = self.inner.outcome(Rc::new(move |state, reward| {
+            without_observations(state, reward, &mut vec![])
+        }));
+        self.outcomes.push(effect);
+        self
+    }
+}
+
+impl<S: State, A: ActionType + 'static, O: Observation> IActionBuilder<S>
+    for ObservingActionBuilder<S, A, O>
+{
+    fn build(&self, action_index: usize) -> Vec<model::Action<S>> {
+        vec![self.inner.build(action_index)]
+    }
+}
+
+/// Builds a `Pomdp` from actions whose outcomes emit observations, like `MdpBuilder` does
+/// for an `Mdp`.
+///
+/// Instead of a single initial state it starts from a distribution over states. All of them
+/// are explored, and the first one becomes state 0.
+pub struct PomdpBuilder<S: State, O: Observation> {
+    initial_belief: Vec<(S, f64)>,
+    actions: Vec<model::Action<S>>,
+    // The outcomes of every action, with their observations.
+    outcomes: Vec<Vec<Rc<ObservingOutcomeFn<S, O>>>>,
+}
+
+impl<S: State + 'static, O: Observation + 'static> PomdpBuilder<S, O> {
+    /// The initial probabilities only need to be in proportion.
+    pub fn new(initial_belief: Vec<(S, f64)>) -> Self {
+        Self {
+            initial_belief,
+            actions: vec![],
+            outcomes: vec![],
+        }
+    }
+
+    pub fn add_action<A: ActionType + 'static>(
+        mut self,
+        action_builder: ObservingActionBuilder<S, A, O>,
+    ) -> Self {
+        self.actions
+            .push(action_builder.inner.build(self.actions.len()));
+        self.outcomes.push(action_builder.outcomes);
+        self
+    }
+
+    /// Panics if outcomes of the same action that lead to the same state emit different
+    /// observations, or if an outcome emits none.
+    pub fn build(self) -> Pomdp<S, O> {
+        let initial_states = self
+            .initial_belief
+            .iter()
+            .map(|(state, _)| state.clone())
+            .collect();
+        let mdp = Mdp::explore(initial_states, self.actions.clone());
+        let indices = mdp
+            .states()
+            .iter()
+            .enumerate()
+            .map(|(i, state)| (state.get_hash(), i))
+            .collect::<HashMap<_, _>>();
+
+        let mut observations = ObservationIndex::default();
+        let mut observation_model: Vec<HashMap<ActionBox, Vec<(usize, f64)>>> =
+            vec![HashMap::new(); mdp.states().len()];
+        for state in mdp.states() {
+            for (action, outcomes) in self.actions.iter().zip(self.outcomes.iter()) {
+                if !action.preconditions_valid(state) {
+                    continue;
+                }
+                // The weighted observations per next state.
+                let mut emitted: HashMap<usize, Vec<(usize, f64)>> = HashMap::new();
+                for outcome in outcomes {
+                    let mut next = state.clone();
+                    let mut emits = vec![];
+                    let probability = outcome(&mut next, &mut 0.0, &mut emits);
+                    if probability == 0.0 {
+                        continue;
+                    }
+                    assert!(
+                        !emits.is_empty(),
+                        "every outcome of {:?} has to emit an observation",
+                        action.action()
+                    );
+                    let next = indices[&next.get_hash()];
+                    for (o, p) in emits {
+                        emitted
+                            .entry(next)
+                            .or_default()
+                            .push((observations.index(o), probability * p));
+                    }
+                }
+
+                for (next, weighted) in emitted {
+                    let distribution = normalised(weighted);
+                    match observation_model[next].get(&action.action()) {
+                        Some(existing) => assert!(
+                            same_distribution(existing, &distribution),
+                            "the observations of {:?} leading to state {} depend on the previous state",
+                            action.action(),
+                            next
+                        ),
+                        None => {
+                            observation_model[next].insert(action.action(), distribution);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut probabilities = vec![0.0; mdp.states().len()];
+        for (state, p) in self.initial_belief.iter() {
+            probabilities[indices[&state.get_hash()]] += p;
+        }
+
+        Pomdp {
+            mdp,
+            observations: observations.observations,
+            observation_model,
+            initial_belief: Belief::new(probabilities),
+        }
+    }
+}
+
+// Merges repeated observations, sorts them and makes the probabilities add up to 1.
+fn normalised(mut weighted: Vec<(usize, f64)>) -> Vec<(usize, f64)> {
+    weighted.sort_by_key(|(o, _)| *o);
+    let total = weighted.iter().map(|(_, p)| p).sum::<f64>();
+    let mut distribution: Vec<(usize, f64)> = vec![];
+    for (o, p) in weighted {
+        match distribution.last_mut() {
+            Some(last) if last.0 == o => last.1 += p / total,
+            _ => distribution.push((o, p / total)),
+        }
+    }
+    distribution
+}
+
+fn same_distribution(a: &[(usize, f64)], b: &[(usize, f64)]) -> bool {
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.0 == y.0 && (x.1 - y.1).abs() < OBSERVATION_EPSILON)
+}
@@ -0,0 +1,171 @@
+use std::time::Instant;
+
+use crate::{
+    model::State,
+    solver::{expected_value, Solver, SolverConfig, SolverError, ValueIterationSolver},
+};
+
+use super::{
+    point_based::{unavailable, AlphaVector, ValueFunction},
+    Observation, Pomdp, Tables,
+};
+
+/// The QMDP approximation: one alpha vector per action, holding the Q-values of the fully
+/// observable `Mdp`.
+///
+/// It assumes the state becomes fully observable after the next step, so it never values
+/// gathering information (the tiger problem's listening) and overestimates every belief.
+/// Cheap, and an upper bound on the value of the `Pomdp`.
+pub struct QmdpSolver<'a, S: State, O: Observation> {
+    pomdp: &'a Pomdp<S, O>,
+    discount: f64,
+    config: SolverConfig,
+}
+
+impl<'a, S: State, O: Observation> QmdpSolver<'a, S, O> {
+    pub fn new(pomdp: &'a Pomdp<S, O>, discount: f64) -> Self {
+        Self {
+            pomdp,
+            discount,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// Passed on to the `ValueIterationSolver` of the underlying `Mdp`.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<ValueFunction, SolverError> {
+        let start = Instant::now();
+        let mdp = self.pomdp.mdp();
+        let solution = ValueIterationSolver::new(mdp, self.discount)
+            .with_config(self.config.clone())
+            .solve()?;
+
+        let tables = Tables::new(self.pomdp);
+        let vectors = tables
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(a, action)| {
+                let mut values = (0..tables.num_states())
+                    .map(|i| match mdp.actions(i).get(action) {
+                        Some(transitions) => {
+                            expected_value(transitions, solution.values(), self.discount)
+                        }
+                        None => 0.0,
+                    })
+                    .collect::<Vec<_>>();
+                unavailable(&tables, a, &mut values);
+                AlphaVector::new(action.clone(), values)
+            })
+            .collect();
+        Ok(ValueFunction::new(
+            vectors,
+            solution.iterations(),
+            start.elapsed(),
+        ))
+    }
+}
+
+/// The fast informed bound (Hauskrecht): one alpha vector per action, like QMDP, but the
+/// backup takes the observation after the next step into account.
+///
+/// `alpha_a(s) = R(s, a) + discount * sum_o max_a' sum_s' T(s, a, s') O(o | a, s') alpha_a'(s')`
+///
+/// It is still an upper bound, but a tighter one than QMDP.
+pub struct FibSolver<'a, S: State, O: Observation> {
+    pomdp: &'a Pomdp<S, O>,
+    discount: f64,
+    config: SolverConfig,
+}
+
+impl<'a, S: State, O: Observation> FibSolver<'a, S, O> {
+    pub fn new(pomdp: &'a Pomdp<S, O>, discount: f64) -> Self {
+        Self {
+            pomdp,
+            discount,
+            config: SolverConfig::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn solve(&mut self) -> Result<ValueFunction, SolverError> {
+        let start = Instant::now();
+        let tables = Tables::new(self.pomdp);
+        let num_actions = tables.actions.len();
+        let mut vectors = (0..num_actions)
+            .map(|a| {
+                let mut values = vec![0.0; tables.num_states()];
+                unavailable(&tables, a, &mut values);
+                values
+            })
+            .collect::<Vec<_>>();
+
+        let mut iterations = 0;
+        loop {
+            let old_vectors = vectors.clone();
+            for (a, values) in vectors.iter_mut().enumerate() {
+                values.clone_from(&tables.rewards[a]);
+                for o in 0..tables.num_observations {
+                    let projected = old_vectors
+                        .iter()
+                        .map(|alpha| tables.project(a, o, alpha))
+                        .collect::<Vec<_>>();
+                    for (s, value) in values.iter_mut().enumerate() {
+                        let best = projected
+                            .iter()
+                            .map(|g| g[s])
+                            .fold(f64::NEG_INFINITY, f64::max);
+                        *value += self.discount * best;
+                    }
+                }
+                unavailable(&tables, a, values);
+            }
+            iterations += 1;
+
+            // Only the entries of available actions ever change.
+            let finite = |vectors: &[Vec<f64>]| {
+                vectors
+                    .iter()
+                    .flatten()
+                    .copied()
+                    .filter(|&v| v != f64::NEG_INFINITY)
+                    .collect::<Vec<_>>()
+            };
+            let (old_values, values) = (finite(&old_vectors), finite(&vectors));
+            let value_function = || {
+                let vectors = tables
+                    .actions
+                    .iter()
+                    .zip(vectors.iter())
+                    .map(|(action, values)| AlphaVector::new(action.clone(), values.clone()))
+                    .collect();
+                ValueFunction::new(vectors, iterations, start.elapsed())
+            };
+
+            if self.config.converged(&old_values, &values) {
+                return Ok(value_function());
+            }
+            if self.config.diverged(&old_values, &values, self.discount) {
+                return Err(SolverError::Diverged { iterations });
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                let residual = values
+                    .iter()
+                    .zip(old_values)
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f64::max);
+                return Err(error(
+                    value_function().solution(tables.num_states(), residual),
+                ));
+            }
+        }
+    }
+}
@@ -0,0 +1,402 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    model::{ActionBox, State},
+    policy::Policy,
+    rng::Rng,
+    solver::{Solution, SolverConfig, SolverError},
+};
+
+use super::{Belief, Observation, Pomdp, Tables};
+
+// Beliefs closer than this (in total variation) count as the same point.
+const BELIEF_EPSILON: f64 = 1e-6;
+
+/// A linear function over beliefs: the value of taking `action` and acting well afterwards,
+/// given as a value per state. Entries are -infinity in states where the action isn't
+/// available, and 0 in terminal states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlphaVector {
+    action: ActionBox,
+    values: Vec<f64>,
+}
+
+impl AlphaVector {
+    pub(crate) fn new(action: ActionBox, values: Vec<f64>) -> Self {
+        Self { action, values }
+    }
+
+    pub fn action(&self) -> &ActionBox {
+        &self.action
+    }
+
+    pub fn values(&self) -> &[f64] {
+        self.values.as_ref()
+    }
+
+    /// The expected value under a belief. Only the states the belief considers possible count.
+    pub fn value(&self, belief: &Belief) -> f64 {
+        belief
+            .support()
+            .map(|i| belief.probability(i) * self.values[i])
+            .sum()
+    }
+}
+
+/// A piecewise linear and convex value function over beliefs, as a set of `AlphaVector`s.
+/// The value of a belief is the best value of any vector, and the policy takes the action of
+/// that vector.
+#[derive(Debug, Clone)]
+pub struct ValueFunction {
+    vectors: Vec<AlphaVector>,
+    iterations: usize,
+    elapsed: Duration,
+}
+
+impl ValueFunction {
+    pub(crate) fn new(vectors: Vec<AlphaVector>, iterations: usize, elapsed: Duration) -> Self {
+        Self {
+            vectors,
+            iterations,
+            elapsed,
+        }
+    }
+
+    pub fn vectors(&self) -> &[AlphaVector] {
+        self.vectors.as_ref()
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The best vector for a belief, or `None` if there are no vectors.
+    pub fn best(&self, belief: &Belief) -> Option<&AlphaVector> {
+        best(&self.vectors, belief)
+    }
+
+    /// The value of a belief. Beliefs without vectors (like terminal ones) are worth 0.
+    pub fn value(&self, belief: &Belief) -> f64 {
+        self.best(belief)
+            .map(|alpha| alpha.value(belief))
+            .unwrap_or_default()
+    }
+
+    pub fn action(&self, belief: &Belief) -> Option<&ActionBox> {
+        self.best(belief).map(|alpha| alpha.action())
+    }
+
+    // What the value function says about every state when it is known for certain, for the
+    // solution in a `SolverError`.
+    pub(crate) fn solution(&self, num_states: usize, residual: f64) -> Solution {
+        let beliefs = (0..num_states)
+            .map(|i| Belief::point(num_states, i))
+            .collect::<Vec<_>>();
+        let values = beliefs.iter().map(|b| self.value(b)).collect();
+        let actions = beliefs.iter().map(|b| self.action(b).cloned()).collect();
+        Solution::new(
+            values,
+            Policy::new(actions),
+            self.iterations,
+            residual,
+            self.elapsed,
+        )
+    }
+}
+
+pub(crate) fn best<'a>(vectors: &'a [AlphaVector], belief: &Belief) -> Option<&'a AlphaVector> {
+    vectors
+        .iter()
+        .map(|alpha| (alpha.value(belief), alpha))
+        .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+        .map(|(_, alpha)| alpha)
+}
+
+// Marks the states where an action isn't available, unless they are terminal.
+pub(crate) fn unavailable(tables: &Tables, action: usize, values: &mut [f64]) {
+    for (state, value) in values.iter_mut().enumerate() {
+        if tables.transitions[action][state].is_empty() && !tables.is_terminal(state) {
+            *value = f64::NEG_INFINITY;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointBasedMethod {
+    /// Backs up every belief point in every iteration (Pineau, Gordon and Thrun).
+    Pbvi,
+    /// Randomized backups that stop as soon as every belief point has improved (Spaan and
+    /// Vlassis). Far fewer backups per iteration, and the value function stays small.
+    Perseus,
+}
+
+/// Point-based value iteration for a discounted `Pomdp`.
+///
+/// Exact POMDP value iteration backs up the whole belief simplex. Point-based methods only
+/// back up a finite set of beliefs, sampled up front by acting randomly from the initial
+/// belief, and keep one alpha vector per point. The value function starts from the lower
+/// bound `min(min reward, 0) / (1 - discount)`, so the discount has to be below 1.
+///
+/// It stops once the values of the belief points change by less than the tolerance of the
+/// `SolverConfig`. Hitting a limit returns an error with a `Solution` for the states known
+/// for certain, and the vectors so far stay available from `value_function`.
+pub struct PointBasedSolver<'a, S: State, O: Observation> {
+    pomdp: &'a Pomdp<S, O>,
+    tables: Tables,
+    discount: f64,
+    method: PointBasedMethod,
+    belief_points: usize,
+    rng: Rng,
+    config: SolverConfig,
+    beliefs: Vec<Belief>,
+    value_function: ValueFunction,
+}
+
+impl<'a, S: State, O: Observation> PointBasedSolver<'a, S, O> {
+    pub fn new(pomdp: &'a Pomdp<S, O>, discount: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&discount),
+            "point-based value iteration needs a discount in [0, 1)"
+        );
+        Self {
+            pomdp,
+            tables: Tables::new(pomdp),
+            discount,
+            method: PointBasedMethod::Perseus,
+            belief_points: 100,
+            rng: Rng::new(0),
+            config: SolverConfig::default(),
+            beliefs: vec![],
+            value_function: ValueFunction::new(vec![], 0, Duration::ZERO),
+        }
+    }
+
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn method(mut self, method: PointBasedMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// How many beliefs to sample. Fewer are used if no new ones turn up.
+    pub fn belief_points(mut self, belief_points: usize) -> Self {
+        self.belief_points = belief_points;
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.rng = Rng::new(seed);
+        self
+    }
+
+    /// The sampled belief points.
+    pub fn beliefs(&self) -> &[Belief] {
+        self.beliefs.as_ref()
+    }
+
+    pub fn value_function(&self) -> &ValueFunction {
+        &self.value_function
+    }
+
+    pub fn solve(&mut self) -> Result<ValueFunction, SolverError> {
+        let start = Instant::now();
+        self.sample_beliefs();
+        if self.beliefs.is_empty() {
+            self.value_function = ValueFunction::new(vec![], 0, start.elapsed());
+            return Ok(self.value_function.clone());
+        }
+        let mut vectors = vec![self.lower_bound()];
+        let mut iterations = 0;
+        loop {
+            let old_values = self.values(&vectors);
+            let projections = self.projections(&vectors);
+            vectors = match self.method {
+                PointBasedMethod::Pbvi => self.pbvi(&projections),
+                PointBasedMethod::Perseus => self.perseus(&vectors, &projections, &old_values),
+            };
+            iterations += 1;
+            let values = self.values(&vectors);
+            self.value_function = ValueFunction::new(vectors.clone(), iterations, start.elapsed());
+
+            if self.config.converged(&old_values, &values) {
+                return Ok(self.value_function.clone());
+            }
+            if self.config.diverged(&old_values, &values, self.discount) {
+                return Err(SolverError::Diverged { iterations });
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                let residual = values
+                    .iter()
+                    .zip(old_values)
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f64::max);
+                return Err(error(
+                    self.value_function
+                        .solution(self.tables.num_states(), residual),
+                ));
+            }
+        }
+    }
+
+    fn sample_beliefs(&mut self) {
+        let initial = self.pomdp.initial_belief().clone();
+        self.beliefs = vec![];
+        if self.pomdp.is_terminal(&initial) {
+            return;
+        }
+        self.beliefs.push(initial);
+        let mut attempts = 0;
+        while self.beliefs.len() < self.belief_points && attempts < 100 * self.belief_points {
+            attempts += 1;
+            let belief = &self.beliefs[self.rng.below(self.beliefs.len())];
+            let actions = self.pomdp.actions(belief);
+            let state = belief.sample(&mut self.rng);
+            if actions.is_empty() || self.pomdp.mdp().actions(state).is_empty() {
+                continue;
+            }
+            let action = &actions[self.rng.below(actions.len())];
+            let (_, observation, _) = self.pomdp.step(state, action, &mut self.rng);
+            let Some(next) = self.pomdp.update(belief, action, observation) else {
+                continue;
+            };
+            let new = self.beliefs.iter().all(|b| {
+                let distance = b
+                    .probabilities()
+                    .iter()
+                    .zip(next.probabilities())
+                    .map(|(p, q)| (p - q).abs())
+                    .sum::<f64>();
+                distance > BELIEF_EPSILON
+            });
+            if new && !self.pomdp.is_terminal(&next) {
+                self.beliefs.push(next);
+            }
+        }
+    }
+
+    // Episodes can end in a terminal state early, so only the worst reward below 0 adds up
+    // to a lower bound over an unlimited number of steps.
+    fn lower_bound(&self) -> AlphaVector {
+        let worst = self
+            .tables
+            .rewards
+            .iter()
+            .flatten()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        let values = (0..self.tables.num_states())
+            .map(|i| {
+                if self.tables.is_terminal(i) {
+                    0.0
+                } else {
+                    worst.min(0.0) / (1.0 - self.discount)
+                }
+            })
+            .collect();
+        AlphaVector {
+            action: self.tables.actions[0].clone(),
+            values,
+        }
+    }
+
+    fn values(&self, vectors: &[AlphaVector]) -> Vec<f64> {
+        self.beliefs
+            .iter()
+            .map(|b| best(vectors, b).unwrap().value(b))
+            .collect()
+    }
+
+    // The projection of every vector back through every action and observation.
+    fn projections(&self, vectors: &[AlphaVector]) -> Vec<Vec<Vec<Vec<f64>>>> {
+        (0..self.tables.actions.len())
+            .map(|a| {
+                (0..self.tables.num_observations)
+                    .map(|o| {
+                        vectors
+                            .iter()
+                            .map(|alpha| self.tables.project(a, o, &alpha.values))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // The best new vector for a belief, one step further than the old ones.
+    fn backup(&self, belief: &Belief, projections: &[Vec<Vec<Vec<f64>>>]) -> AlphaVector {
+        self.tables
+            .indices(&self.pomdp.actions(belief))
+            .into_iter()
+            .map(|a| {
+                let mut values = self.tables.rewards[a].clone();
+                for projected in projections[a].iter() {
+                    let best = projected
+                        .iter()
+                        .map(|g| (dot(belief, g), g))
+                        .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                        .map(|(_, g)| g)
+                        .unwrap();
+                    for (value, g) in values.iter_mut().zip(best) {
+                        *value += self.discount * g;
+                    }
+                }
+                unavailable(&self.tables, a, &mut values);
+                let alpha = AlphaVector {
+                    action: self.tables.actions[a].clone(),
+                    values,
+                };
+                (alpha.value(belief), alpha)
+            })
+            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+            .map(|(_, alpha)| alpha)
+            .unwrap()
+    }
+
+    fn pbvi(&self, projections: &[Vec<Vec<Vec<f64>>>]) -> Vec<AlphaVector> {
+        let mut new_vectors: Vec<AlphaVector> = vec![];
+        for belief in self.beliefs.iter() {
+            let alpha = self.backup(belief, projections);
+            if !new_vectors.contains(&alpha) {
+                new_vectors.push(alpha);
+            }
+        }
+        new_vectors
+    }
+
+    fn perseus(
+        &mut self,
+        vectors: &[AlphaVector],
+        projections: &[Vec<Vec<Vec<f64>>>],
+        old_values: &[f64],
+    ) -> Vec<AlphaVector> {
+        let mut new_vectors: Vec<AlphaVector> = vec![];
+        // The belief points that haven't improved yet.
+        let mut remaining = (0..self.beliefs.len()).collect::<Vec<_>>();
+        while !remaining.is_empty() {
+            let k = remaining[self.rng.below(remaining.len())];
+            let belief = &self.beliefs[k];
+            let mut alpha = self.backup(belief, projections);
+            if alpha.value(belief) < old_values[k] {
+                alpha = best(vectors, belief).unwrap().clone();
+            }
+            remaining.retain(|&j| alpha.value(&self.beliefs[j]) < old_values[j]);
+            new_vectors.push(alpha);
+        }
+        new_vectors
+    }
+}
+
+// Like `AlphaVector::value`, for vectors that aren't one yet.
+fn dot(belief: &Belief, values: &[f64]) -> f64 {
+    belief
+        .support()
+        .map(|i| belief.probability(i) * values[i])
+        .sum()
+}
@@ -1,12 +1,341 @@
-use crate::{mdp::Mdp, model::State, policy::Policy};
+use std::{
+    error::Error,
+    fmt::Display,
+    time::{Duration, Instant},
+};
 
-const EPSILON: f64 = 0.00001;
+use crate::{
+    graph::strongly_connected_components,
+    linalg::solve_dense,
+    mdp::{Mdp, Transition},
+    model::{ActionBox, State},
+    policy::Policy,
+    rng::Rng,
+};
+
+mod average_reward;
+mod evaluation;
+mod finite_horizon;
+mod lao_star;
+mod lp;
+mod prioritized;
+mod rtdp;
+mod shortest_path;
+mod topological;
+
+pub use average_reward::AverageRewardSolver;
+pub use evaluation::{EvaluationMethod, PolicyEvaluator};
+pub use finite_horizon::FiniteHorizonSolver;
+pub use lao_star::LaoStarSolver;
+pub use lp::LpSolver;
+pub use prioritized::PrioritizedSweepingSolver;
+pub use rtdp::{LrtdpSolver, RtdpSolver};
+pub use shortest_path::StochasticShortestPathSolver;
+pub use topological::TopologicalSolver;
+
+// Expected reward of taking an action, followed by the (discounted) value of where it leads.
+pub(crate) fn expected_value(transitions: &[Transition], values: &[f64], discount: f64) -> f64 {
+    let mut expected_reward = 0.0;
+    for t in transitions {
+        expected_reward += t.probability() * (t.reward() + discount * values[t.to()]);
+    }
+    expected_reward
+}
+
+// Best expected value over the actions available in a state, or 0 if there are none.
+pub(crate) fn best_value<S: State>(
+    mdp: &Mdp<S>,
+    state: usize,
+    values: &[f64],
+    discount: f64,
+) -> f64 {
+    mdp.actions(state)
+        .values()
+        .map(|transitions| expected_value(transitions, values, discount))
+        .reduce(|accum, item| if accum >= item { accum } else { item })
+        .unwrap_or_default()
+}
+
+// Largest change a single Bellman backup would make to any state value.
+pub(crate) fn bellman_residual<S: State>(mdp: &Mdp<S>, values: &[f64], discount: f64) -> f64 {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| {
+            let backup = best_value(mdp, i, values, discount);
+            if backup == value {
+                0.0
+            } else {
+                (backup - value).abs()
+            }
+        })
+        .fold(0.0, f64::max)
+}
+
+pub(crate) fn greedy_policy<S: State>(mdp: &Mdp<S>, values: &[f64], discount: f64) -> Policy {
+    let actions = (0..mdp.states().len())
+        .map(|index| {
+            mdp.sorted_actions(index)
+                .into_iter()
+                .map(|(action, transitions)| {
+                    (expected_value(transitions, values, discount), action)
+                })
+                .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                .map(|(_, name)| name.clone())
+        })
+        .collect();
+
+    Policy::new(actions)
+}
+
+/// Everything a solver found out about an `Mdp`.
+#[derive(Debug, Clone)]
+pub struct Solution {
+    values: Vec<f64>,
+    policy: Policy,
+    iterations: usize,
+    residual: f64,
+    elapsed: Duration,
+}
+
+impl Solution {
+    pub fn new(
+        values: Vec<f64>,
+        policy: Policy,
+        iterations: usize,
+        residual: f64,
+        elapsed: Duration,
+    ) -> Self {
+        Self {
+            values,
+            policy,
+            iterations,
+            residual,
+            elapsed,
+        }
+    }
+
+    pub fn values(&self) -> &[f64] {
+        self.values.as_ref()
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    /// Number of sweeps (or improvement rounds) the solver ran.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Bellman residual of the final values.
+    pub fn residual(&self) -> f64 {
+        self.residual
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Largest difference between the state values of two solutions of the same `Mdp`.
+    pub fn max_value_difference(&self, other: &Solution) -> f64 {
+        self.values
+            .iter()
+            .zip(other.values.iter())
+            .map(|(a, b)| if a == b { 0.0 } else { (a - b).abs() })
+            .fold(0.0, f64::max)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StoppingRule {
+    /// Stop once every state value changes by less than the tolerance.
+    MaxNorm,
+    /// Stop once the spread (max - min) of the changes is below the tolerance.
+    /// The values may still be drifting, but the greedy policy no longer changes, which also
+    /// makes this usable on undiscounted continuing models.
+    SpanSeminorm,
+}
+
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    absolute_tolerance: f64,
+    relative_tolerance: f64,
+    max_iterations: Option<usize>,
+    timeout: Option<Duration>,
+    stopping_rule: StoppingRule,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            absolute_tolerance: 0.00001,
+            relative_tolerance: 0.0,
+            max_iterations: None,
+            timeout: None,
+            stopping_rule: StoppingRule::MaxNorm,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn absolute_tolerance(mut self, tolerance: f64) -> Self {
+        self.absolute_tolerance = tolerance;
+        self
+    }
+
+    /// Tolerance as a fraction of the magnitude of the value being compared.
+    pub fn relative_tolerance(mut self, tolerance: f64) -> Self {
+        self.relative_tolerance = tolerance;
+        self
+    }
+
+    pub fn max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = Some(max_iterations);
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn stopping_rule(mut self, stopping_rule: StoppingRule) -> Self {
+        self.stopping_rule = stopping_rule;
+        self
+    }
+
+    pub(crate) fn tolerance(&self, magnitude: f64) -> f64 {
+        self.absolute_tolerance + self.relative_tolerance * magnitude.abs()
+    }
+
+    pub(crate) fn converged(&self, old_values: &[f64], values: &[f64]) -> bool {
+        match self.stopping_rule {
+            StoppingRule::MaxNorm => values
+                .iter()
+                .zip(old_values.iter())
+                .all(|(a, b)| (a - b).abs() < self.tolerance(*a)),
+            StoppingRule::SpanSeminorm => {
+                let (low, high) = difference_range(old_values, values);
+                let (value_low, value_high) = difference_range(&vec![0.0; values.len()], values);
+                high - low < self.tolerance(value_high - value_low)
+            }
+        }
+    }
+
+    // Without discounting, changes that are all (nearly) the same but not small mean the
+    // values will keep growing by that amount every sweep. Periodic models can slip past this,
+    // so an iteration limit or timeout is still the only guarantee that `solve()` returns.
+    pub(crate) fn diverged(&self, old_values: &[f64], values: &[f64], discount: f64) -> bool {
+        if values.iter().any(|v| !v.is_finite()) {
+            return true;
+        }
+        if discount < 1.0 || self.stopping_rule == StoppingRule::SpanSeminorm {
+            return false;
+        }
+        let (low, high) = difference_range(old_values, values);
+        high - low < self.absolute_tolerance && !self.converged(old_values, values)
+    }
+
+    // Which limit, if any, stops the solver after the given number of iterations.
+    pub(crate) fn exceeded(
+        &self,
+        iterations: usize,
+        start: Instant,
+    ) -> Option<fn(Solution) -> SolverError> {
+        if self.max_iterations.is_some_and(|max| iterations >= max) {
+            Some(SolverError::IterationLimit)
+        } else if self
+            .timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            Some(SolverError::Timeout)
+        } else {
+            None
+        }
+    }
+}
+
+fn difference_range(old_values: &[f64], values: &[f64]) -> (f64, f64) {
+    values
+        .iter()
+        .zip(old_values.iter())
+        .map(|(a, b)| a - b)
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), d| {
+            (low.min(d), high.max(d))
+        })
+}
+
+/// Why a solver stopped without converging.
+#[derive(Debug)]
+pub enum SolverError {
+    /// Ran out of iterations. Holds the solution found so far.
+    IterationLimit(Solution),
+    /// Ran out of time. Holds the solution found so far.
+    Timeout(Solution),
+    /// The values grow without bound, e.g. an undiscounted model with a rewarding loop.
+    Diverged { iterations: usize },
+    /// No policy satisfies the constraints of the problem, e.g. a cost budget that is too tight.
+    Infeasible,
+}
+
+impl Display for SolverError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolverError::IterationLimit(solution) => write!(
+                f,
+                "stopped after {} iterations without converging (residual {})",
+                solution.iterations(),
+                solution.residual()
+            ),
+            SolverError::Timeout(solution) => write!(
+                f,
+                "timed out after {:?} without converging (residual {})",
+                solution.elapsed(),
+                solution.residual()
+            ),
+            SolverError::Diverged { iterations } => {
+                write!(f, "values diverged after {} iterations", iterations)
+            }
+            SolverError::Infeasible => write!(f, "no policy satisfies the constraints"),
+        }
+    }
+}
+
+impl Error for SolverError {}
+
+pub trait Solver<S: State> {
+    /// Runs the solver. Returns `Ok` only if it converged.
+    fn solve(&mut self) -> Result<Solution, SolverError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateMode {
+    /// Every backup in a sweep reads the values from the previous sweep.
+    Jacobi,
+    /// Backups read values already updated earlier in the same sweep.
+    GaussSeidel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SweepOrder {
+    Index,
+    Reverse,
+    /// A fresh permutation every sweep, from the given seed.
+    Random(u64),
+    /// Successors before predecessors, following the exploration from the initial state.
+    /// On chain-shaped models this lets one Gauss-Seidel sweep carry values all the way back.
+    ReverseTopological,
+}
 
 pub struct ValueIterationSolver<'a, S: State> {
     values: Vec<f64>,
     old_values: Vec<f64>,
     mdp: &'a Mdp<S>,
     discount: f64,
+    config: SolverConfig,
+    update_mode: UpdateMode,
+    sweep_order: SweepOrder,
 }
 
 impl<'a, S: State> ValueIterationSolver<'a, S> {
@@ -16,73 +345,322 @@ impl<'a, S: State> ValueIterationSolver<'a, S> {
             old_values: vec![0.0; mdp.states().len()],
             mdp,
             discount,
+            config: SolverConfig::default(),
+            update_mode: UpdateMode::Jacobi,
+            sweep_order: SweepOrder::Index,
         }
     }
 
-    fn iterate(&mut self) {
-        // Save the old values, so we can compare and stop when the change is small enough.
-        self.old_values.clone_from(&self.values);
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
 
-        for i in 0..self.mdp.states().len() {
-            let actions = self.mdp.actions(i);
-            let max_value = actions
-                .iter()
-                .map(|(_, transitions)| {
-                    let mut expected_reward = 0.0;
-                    for t in transitions {
-                        expected_reward += t.probability()
-                            * (t.reward() + self.discount * self.old_values[t.to()]);
-                    }
-                    expected_reward
-                })
-                .reduce(|accum, item| if accum >= item { accum } else { item })
-                .unwrap_or_default();
+    pub fn update_mode(mut self, update_mode: UpdateMode) -> Self {
+        self.update_mode = update_mode;
+        self
+    }
 
-            self.values[i] = max_value;
+    pub fn sweep_order(mut self, sweep_order: SweepOrder) -> Self {
+        self.sweep_order = sweep_order;
+        self
+    }
+
+    fn initial_order(&self) -> Vec<usize> {
+        let n = self.mdp.states().len();
+        match self.sweep_order {
+            SweepOrder::Index | SweepOrder::Random(_) => (0..n).collect(),
+            SweepOrder::Reverse => (0..n).rev().collect(),
+            SweepOrder::ReverseTopological => strongly_connected_components(&self.mdp.successors())
+                .into_iter()
+                .flatten()
+                .collect(),
         }
     }
 
-    pub fn solve(&mut self) {
-        loop {
-            self.iterate();
-            let is_done = self
-                .values
-                .iter()
-                .zip(self.old_values.iter())
-                .all(|(a, b)| (*a - *b).abs() < EPSILON);
-            if is_done {
-                break;
+    fn solution(&self, iterations: usize, start: Instant) -> Solution {
+        Solution::new(
+            self.values.clone(),
+            greedy_policy(self.mdp, &self.values, self.discount),
+            iterations,
+            bellman_residual(self.mdp, &self.values, self.discount),
+            start.elapsed(),
+        )
+    }
+
+    fn iterate(&mut self, order: &[usize]) {
+        match self.update_mode {
+            UpdateMode::Jacobi => {
+                // Save the old values, so we can compare and stop when the change is small enough.
+                self.old_values.clone_from(&self.values);
+                for &i in order {
+                    self.values[i] = best_value(self.mdp, i, &self.old_values, self.discount);
+                }
+            }
+            UpdateMode::GaussSeidel => {
+                for &i in order {
+                    self.old_values[i] = self.values[i];
+                    self.values[i] = best_value(self.mdp, i, &self.values, self.discount);
+                }
             }
         }
     }
+}
 
-    pub fn values(&self) -> &[f64] {
-        self.values.as_ref()
+impl<'a, S: State> Solver<S> for ValueIterationSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let mut order = self.initial_order();
+        let mut rng = match self.sweep_order {
+            SweepOrder::Random(seed) => Some(Rng::new(seed)),
+            _ => None,
+        };
+        let mut iterations = 0;
+        loop {
+            if let Some(rng) = rng.as_mut() {
+                rng.shuffle(&mut order);
+            }
+            self.iterate(&order);
+            iterations += 1;
+            if self.config.converged(&self.old_values, &self.values) {
+                return Ok(self.solution(iterations, start));
+            }
+            if self
+                .config
+                .diverged(&self.old_values, &self.values, self.discount)
+            {
+                return Err(SolverError::Diverged { iterations });
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                return Err(error(self.solution(iterations, start)));
+            }
+        }
     }
+}
 
-    pub fn get_policy(&self) -> Policy {
-        let actions = self
-            .mdp
-            .states()
+// Computes the exact values of a deterministic policy.
+pub(crate) fn evaluate_policy<S: State>(
+    mdp: &Mdp<S>,
+    actions: &[Option<ActionBox>],
+    discount: f64,
+) -> Vec<f64> {
+    let distributions = actions
+        .iter()
+        .map(|action| action.iter().map(|a| (a.clone(), 1.0)).collect())
+        .collect::<Vec<Vec<_>>>();
+    evaluate_stochastic_policy(mdp, &distributions, discount)
+}
+
+// Computes the exact values of a policy that picks each action with some probability.
+// States with no actions in their distribution are worth 0.
+// The states are split into strongly connected components under the policy, and each
+// component is solved as a small linear system once everything downstream of it is known.
+pub(crate) fn evaluate_stochastic_policy<S: State>(
+    mdp: &Mdp<S>,
+    distributions: &[Vec<(ActionBox, f64)>],
+    discount: f64,
+) -> Vec<f64> {
+    let n = mdp.states().len();
+    // (probability, transition) of every outcome the policy can reach from a state.
+    let transitions = |state: usize| {
+        distributions[state]
             .iter()
-            .enumerate()
-            .map(|(index, _)| {
-                self.mdp
-                    .actions(index)
+            .filter(|(_, p)| *p > 0.0)
+            .flat_map(move |(action, p)| {
+                mdp.actions(state)[action]
                     .iter()
-                    .map(|(action, transitions)| {
-                        let mut expected_reward = 0.0;
-                        for t in transitions {
-                            expected_reward += t.probability()
-                                * (t.reward() + self.discount * self.old_values[t.to()]);
-                        }
-                        (expected_reward, action)
-                    })
-                    .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
-                    .map(|(_, name)| name.clone())
+                    .map(move |t| (p * t.probability(), t))
             })
-            .collect();
+    };
+    let successors = (0..n)
+        .map(|i| {
+            transitions(i)
+                .filter(|(p, _)| *p > 0.0)
+                .map(|(_, t)| t.to())
+                .collect()
+        })
+        .collect::<Vec<Vec<usize>>>();
+
+    let mut values = vec![0.0; n];
+    let mut local_index = vec![usize::MAX; n];
+    for component in strongly_connected_components(&successors) {
+        for (local, &state) in component.iter().enumerate() {
+            local_index[state] = local;
+        }
+
+        let size = component.len();
+        let mut a = vec![vec![0.0; size]; size];
+        let mut b = vec![0.0; size];
+        for (row, &state) in component.iter().enumerate() {
+            a[row][row] = 1.0;
+            for (probability, t) in transitions(state) {
+                b[row] += probability * t.reward();
+                let weight = discount * probability;
+                if weight == 0.0 {
+                    continue;
+                }
+                if local_index[t.to()] != usize::MAX {
+                    a[row][local_index[t.to()]] -= weight;
+                } else {
+                    b[row] += weight * values[t.to()];
+                }
+            }
+        }
+
+        let total: f64 = b.iter().sum();
+        let solution = if !total.is_finite() {
+            // Part of the component can reach a state that never stops collecting reward,
+            // so every state in it can too.
+            Some(vec![total; size])
+        } else {
+            solve_dense(a.clone(), b.clone())
+        };
+
+        // A singular system means the policy loops forever in a closed component without
+        // discounting, which is worth +/- infinity depending on the reward per step, unless
+        // that is 0.
+        let solution = solution.unwrap_or_else(|| {
+            let gain = stationary_gain(a, &b);
+            let scale: f64 = b.iter().map(|r| r.abs()).sum();
+            let value = if gain.abs() <= 1e-12 * scale {
+                0.0
+            } else {
+                gain.signum() * f64::INFINITY
+            };
+            vec![value; size]
+        });
+
+        for (&state, value) in component.iter().zip(solution) {
+            values[state] = value;
+            local_index[state] = usize::MAX;
+        }
+    }
+
+    values
+}
+
+// The long run reward per step of a closed component, given `a = I - P` and the expected
+// reward `b` of every state in it. Solves `pi (I - P) = 0` with the probabilities summing to 1
+// for the stationary distribution `pi`, and weighs the rewards by it.
+fn stationary_gain(a: Vec<Vec<f64>>, b: &[f64]) -> f64 {
+    let size = b.len();
+    let mut transposed = (0..size)
+        .map(|j| (0..size).map(|i| a[i][j]).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut rhs = vec![0.0; size];
+    transposed[size - 1] = vec![1.0; size];
+    rhs[size - 1] = 1.0;
+    match solve_dense(transposed, rhs) {
+        Some(pi) => pi.iter().zip(b).map(|(p, r)| p * r).sum(),
+        // Not a single recurrent class, fall back to the one step rewards.
+        None => b.iter().sum(),
+    }
+}
+
+pub struct PolicyIterationSolver<'a, S: State> {
+    values: Vec<f64>,
+    actions: Vec<Option<ActionBox>>,
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    improvements: usize,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> PolicyIterationSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        Self {
+            values: vec![0.0; mdp.states().len()],
+            actions: vec![None; mdp.states().len()],
+            mdp,
+            discount,
+            improvements: 0,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// Only the iteration limit, timeout and absolute tolerance apply, since every round
+    /// evaluates the policy exactly. The tolerance is how much better an action has to be
+    /// before the policy switches to it.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn solution(&self, start: Instant) -> Solution {
+        Solution::new(
+            self.values.clone(),
+            Policy::new(self.actions.clone()),
+            self.improvements,
+            bellman_residual(self.mdp, &self.values, self.discount),
+            start.elapsed(),
+        )
+    }
+
+    fn evaluate(&mut self) {
+        self.values = evaluate_policy(self.mdp, &self.actions, self.discount);
+    }
+
+    // Makes the policy greedy with respect to the current values.
+    // Returns false once no state can be improved any further.
+    fn improve(&mut self) -> bool {
+        self.improvements += 1;
+        let mut changed = false;
+
+        for i in 0..self.mdp.states().len() {
+            let best = self
+                .mdp
+                .sorted_actions(i)
+                .into_iter()
+                .map(|(action, transitions)| {
+                    (
+                        expected_value(transitions, &self.values, self.discount),
+                        action,
+                    )
+                })
+                .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item });
+
+            let Some((best_value, best_action)) = best else {
+                continue;
+            };
+
+            // Only switch actions for a real improvement, otherwise ties can cycle forever.
+            let current_value = self.actions[i].as_ref().map(|action| {
+                expected_value(&self.mdp.actions(i)[action], &self.values, self.discount)
+            });
+            match current_value {
+                Some(current_value)
+                    if best_value <= current_value + self.config.absolute_tolerance => {}
+                _ => {
+                    self.actions[i] = Some(best_action.clone());
+                    changed = true;
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+impl<'a, S: State> Solver<S> for PolicyIterationSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        while self.improve() {
+            self.evaluate();
+            // Only an endlessly rewarding loop is a problem, we are maximising.
+            if self
+                .values
+                .iter()
+                .any(|v| v.is_nan() || *v == f64::INFINITY)
+            {
+                return Err(SolverError::Diverged {
+                    iterations: self.improvements,
+                });
+            }
+            if let Some(error) = self.config.exceeded(self.improvements, start) {
+                return Err(error(self.solution(start)));
+            }
+        }
 
-        Policy::new(actions)
+        Ok(self.solution(start))
     }
 }
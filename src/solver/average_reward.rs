@@ -0,0 +1,311 @@
+use std::time::Instant;
+
+use crate::{
+    graph::strongly_connected_components,
+    linalg::solve_dense,
+    mdp::{Mdp, Transition},
+    model::{ActionBox, State},
+    policy::Policy,
+};
+
+use super::{expected_value, Solution, Solver, SolverConfig, SolverError};
+
+// Expected value of where an action leads, without any reward.
+fn expected_next(transitions: &[Transition], values: &[f64]) -> f64 {
+    transitions
+        .iter()
+        .map(|t| t.probability() * values[t.to()])
+        .sum()
+}
+
+/// Average-reward policy iteration (Howard's multichain algorithm).
+///
+/// Maximises the long run reward per step instead of a discounted sum, for continuing tasks
+/// without a natural discount factor. Every policy is evaluated exactly to get its gain `g`
+/// (reward per step) and bias `h` (how much better a state is than the average), with `h = 0`
+/// at one state of every recurrent class. States without actions are treated as absorbing
+/// with no reward.
+///
+/// On a unichain model the gain is the same for every state. If the optimal policy has more
+/// than one recurrent class the model is multichain and each state gets its own gain, see
+/// `recurrent_classes()`.
+///
+/// The `Solution` values are the bias, and `Solution::iterations` counts improvement rounds.
+pub struct AverageRewardSolver<'a, S: State> {
+    gain: Vec<f64>,
+    bias: Vec<f64>,
+    actions: Vec<Option<ActionBox>>,
+    recurrent_classes: Vec<Vec<usize>>,
+    mdp: &'a Mdp<S>,
+    improvements: usize,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> AverageRewardSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>) -> Self {
+        let n = mdp.states().len();
+        Self {
+            gain: vec![0.0; n],
+            bias: vec![0.0; n],
+            actions: vec![None; n],
+            recurrent_classes: vec![],
+            mdp,
+            improvements: 0,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// Only the iteration limit, timeout and absolute tolerance apply. The tolerance is how
+    /// much better an action has to be before the policy switches to it.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Long run reward per step, starting from each state.
+    pub fn gain(&self) -> &[f64] {
+        self.gain.as_ref()
+    }
+
+    pub fn bias(&self) -> &[f64] {
+        self.bias.as_ref()
+    }
+
+    /// The closed sets of states the current policy ends up cycling in.
+    pub fn recurrent_classes(&self) -> &[Vec<usize>] {
+        self.recurrent_classes.as_ref()
+    }
+
+    pub fn is_unichain(&self) -> bool {
+        self.recurrent_classes.len() == 1
+    }
+
+    fn transitions(&self, state: usize) -> &'a [Transition] {
+        let mdp = self.mdp;
+        match &self.actions[state] {
+            Some(action) => &mdp.actions(state)[action],
+            None => &[],
+        }
+    }
+
+    // The outcomes of the current action that can actually happen.
+    fn possible_transitions(&self, state: usize) -> impl Iterator<Item = &'a Transition> {
+        self.transitions(state)
+            .iter()
+            .filter(|t| t.probability() > 0.0)
+    }
+
+    // Solves `g = P g` and `g + h = r + P h` for the current policy, one strongly connected
+    // component at a time. Closed components are the recurrent classes, the rest are
+    // transient and only depend on components that were already solved.
+    fn evaluate(&mut self) -> Result<(), SolverError> {
+        let n = self.mdp.states().len();
+        let successors = (0..n)
+            .map(|i| match self.actions[i] {
+                Some(_) => self.possible_transitions(i).map(|t| t.to()).collect(),
+                None => vec![i],
+            })
+            .collect::<Vec<Vec<usize>>>();
+
+        self.recurrent_classes.clear();
+        let mut local_index = vec![usize::MAX; n];
+        for component in strongly_connected_components(&successors) {
+            for (local, &state) in component.iter().enumerate() {
+                local_index[state] = local;
+            }
+
+            let closed = component
+                .iter()
+                .all(|&i| successors[i].iter().all(|&j| local_index[j] != usize::MAX));
+            if closed {
+                self.evaluate_recurrent(&component, &local_index)?;
+                self.recurrent_classes.push(component.clone());
+            } else {
+                self.evaluate_transient(&component, &local_index)?;
+            }
+
+            for &state in component.iter() {
+                local_index[state] = usize::MAX;
+            }
+        }
+        Ok(())
+    }
+
+    // The systems are only singular when rounding breaks them, since every class is solved
+    // after everything it leads to.
+    fn singular(&self) -> SolverError {
+        SolverError::Diverged {
+            iterations: self.improvements,
+        }
+    }
+
+    // Unknowns are the gain of the class, then the bias of every state but the first.
+    fn evaluate_recurrent(
+        &mut self,
+        component: &[usize],
+        local_index: &[usize],
+    ) -> Result<(), SolverError> {
+        let size = component.len();
+        let mut a = vec![vec![0.0; size]; size];
+        let mut b = vec![0.0; size];
+        for (row, &state) in component.iter().enumerate() {
+            a[row][0] = 1.0;
+            if row != 0 {
+                a[row][row] += 1.0;
+            }
+            for t in self.possible_transitions(state) {
+                b[row] += t.probability() * t.reward();
+                let column = local_index[t.to()];
+                if column != 0 {
+                    a[row][column] -= t.probability();
+                }
+            }
+        }
+
+        let x = solve_dense(a, b).ok_or_else(|| self.singular())?;
+        for (row, &state) in component.iter().enumerate() {
+            self.gain[state] = x[0];
+            self.bias[state] = if row == 0 { 0.0 } else { x[row] };
+        }
+        Ok(())
+    }
+
+    fn evaluate_transient(
+        &mut self,
+        component: &[usize],
+        local_index: &[usize],
+    ) -> Result<(), SolverError> {
+        let size = component.len();
+        let mut a = vec![vec![0.0; size]; size];
+        let mut gain = vec![0.0; size];
+        let mut bias = vec![0.0; size];
+        for (row, &state) in component.iter().enumerate() {
+            a[row][row] = 1.0;
+            for t in self.possible_transitions(state) {
+                bias[row] += t.probability() * t.reward();
+                match local_index[t.to()] {
+                    usize::MAX => {
+                        gain[row] += t.probability() * self.gain[t.to()];
+                        bias[row] += t.probability() * self.bias[t.to()];
+                    }
+                    column => a[row][column] -= t.probability(),
+                }
+            }
+        }
+
+        let gain = solve_dense(a.clone(), gain).ok_or_else(|| self.singular())?;
+        for (b, g) in bias.iter_mut().zip(gain.iter()) {
+            *b -= g;
+        }
+        let bias = solve_dense(a, bias).ok_or_else(|| self.singular())?;
+        for (row, &state) in component.iter().enumerate() {
+            self.gain[state] = gain[row];
+            self.bias[state] = bias[row];
+        }
+        Ok(())
+    }
+
+    // Actions whose expected next gain is within the tolerance of the best one.
+    fn gain_optimal(&self, state: usize) -> Vec<(&'a ActionBox, &'a [Transition])> {
+        let mdp = self.mdp;
+        let actions = mdp.actions(state);
+        let best = actions
+            .values()
+            .map(|transitions| expected_next(transitions, &self.gain))
+            .fold(f64::NEG_INFINITY, f64::max);
+        mdp.sorted_actions(state)
+            .into_iter()
+            .filter(|(_, transitions)| {
+                expected_next(transitions, &self.gain) >= best - self.config.absolute_tolerance
+            })
+            .map(|(action, transitions)| (action, transitions.as_slice()))
+            .collect()
+    }
+
+    // First improves the gain anywhere it can. Only once that is stable, improves the bias
+    // among the gain optimal actions. Returns false when neither changes anything.
+    fn improve(&mut self) -> bool {
+        self.improvements += 1;
+        let n = self.mdp.states().len();
+        let tolerance = self.config.absolute_tolerance;
+
+        let mut changed = false;
+        for i in 0..n {
+            let candidates = self.gain_optimal(i);
+            let keep = self.actions[i]
+                .as_ref()
+                .is_some_and(|current| candidates.iter().any(|(a, _)| *a == current));
+            if candidates.is_empty() || keep {
+                continue;
+            }
+            let best = candidates
+                .into_iter()
+                .map(|(action, transitions)| (expected_value(transitions, &self.bias, 1.0), action))
+                .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                .map(|(_, action)| action.clone());
+            self.actions[i] = best;
+            changed = true;
+        }
+        if changed {
+            return true;
+        }
+
+        for i in 0..n {
+            let current = expected_value(self.transitions(i), &self.bias, 1.0);
+            let best = self
+                .gain_optimal(i)
+                .into_iter()
+                .map(|(action, transitions)| (expected_value(transitions, &self.bias, 1.0), action))
+                .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item });
+            if let Some((value, action)) = best {
+                if value > current + tolerance {
+                    self.actions[i] = Some(action.clone());
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+
+    // How far the gain and bias are from satisfying the average-reward optimality equations.
+    fn residual(&self) -> f64 {
+        (0..self.mdp.states().len())
+            .map(|i| {
+                let best = self
+                    .gain_optimal(i)
+                    .into_iter()
+                    .map(|(_, transitions)| expected_value(transitions, &self.bias, 1.0))
+                    .fold(f64::NEG_INFINITY, f64::max);
+                if best == f64::NEG_INFINITY {
+                    self.gain[i].abs()
+                } else {
+                    (best - self.gain[i] - self.bias[i]).abs()
+                }
+            })
+            .fold(0.0, f64::max)
+    }
+
+    fn solution(&self, start: Instant) -> Solution {
+        Solution::new(
+            self.bias.clone(),
+            Policy::new(self.actions.clone()),
+            self.improvements,
+            self.residual(),
+            start.elapsed(),
+        )
+    }
+}
+
+impl<'a, S: State> Solver<S> for AverageRewardSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        while self.improve() {
+            self.evaluate()?;
+            if let Some(error) = self.config.exceeded(self.improvements, start) {
+                return Err(error(self.solution(start)));
+            }
+        }
+
+        Ok(self.solution(start))
+    }
+}
@@ -0,0 +1,144 @@
+use std::time::Instant;
+
+use crate::{
+    mdp::Mdp,
+    model::{ActionBox, State},
+    policy::{Policy, StochasticPolicy},
+};
+
+use super::{evaluate_stochastic_policy, expected_value, Solution, SolverConfig, SolverError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvaluationMethod {
+    /// Solves the linear system `V = r + discount * P V` of the policy. The states are split
+    /// into strongly connected components first, so only the states that reach each other
+    /// end up in the same dense system.
+    Exact,
+    /// Repeats the backup `V = r + discount * P V` from 0 until it converges, according to
+    /// the `SolverConfig`.
+    Iterative,
+}
+
+/// Computes the values of a given policy, rather than of the optimal one.
+///
+/// Policies are either a deterministic `Policy` or a `StochasticPolicy`. States without an
+/// action are treated as terminal and are worth 0. The actions have to be available in their
+/// state.
+///
+/// Exact evaluation of a policy that loops forever without discounting gives +/- infinity
+/// for the states in the loop (or 0 if it never collects reward), where iterative evaluation
+/// returns `SolverError::Diverged`.
+pub struct PolicyEvaluator<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    method: EvaluationMethod,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> PolicyEvaluator<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        Self {
+            mdp,
+            discount,
+            method: EvaluationMethod::Exact,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// Only used by `EvaluationMethod::Iterative`.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn method(mut self, method: EvaluationMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// The `Solution` holds the values and the policy itself, and its residual is the largest
+    /// change one more backup of the policy would make.
+    pub fn evaluate(&self, policy: &Policy) -> Result<Solution, SolverError> {
+        let stochastic = StochasticPolicy::from(policy);
+        self.evaluate_distributions(stochastic.distributions(), policy.clone())
+    }
+
+    /// Like `evaluate`, but the policy in the `Solution` is the greedy projection
+    /// (`StochasticPolicy::greedy`).
+    pub fn evaluate_stochastic(&self, policy: &StochasticPolicy) -> Result<Solution, SolverError> {
+        self.evaluate_distributions(policy.distributions(), policy.greedy())
+    }
+
+    fn evaluate_distributions(
+        &self,
+        distributions: &[Vec<(ActionBox, f64)>],
+        policy: Policy,
+    ) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        match self.method {
+            EvaluationMethod::Exact => {
+                let values = evaluate_stochastic_policy(self.mdp, distributions, self.discount);
+                let residual = self.residual(distributions, &values);
+                Ok(Solution::new(values, policy, 1, residual, start.elapsed()))
+            }
+            EvaluationMethod::Iterative => {
+                let mut values = vec![0.0; self.mdp.states().len()];
+                let mut iterations = 0;
+                loop {
+                    let old_values = values;
+                    values = (0..old_values.len())
+                        .map(|i| self.backup(distributions, i, &old_values))
+                        .collect();
+                    iterations += 1;
+
+                    if self.config.converged(&old_values, &values) {
+                        let residual = self.residual(distributions, &values);
+                        return Ok(Solution::new(
+                            values,
+                            policy,
+                            iterations,
+                            residual,
+                            start.elapsed(),
+                        ));
+                    }
+                    if self.config.diverged(&old_values, &values, self.discount) {
+                        return Err(SolverError::Diverged { iterations });
+                    }
+                    if let Some(error) = self.config.exceeded(iterations, start) {
+                        let residual = self.residual(distributions, &values);
+                        return Err(error(Solution::new(
+                            values,
+                            policy,
+                            iterations,
+                            residual,
+                            start.elapsed(),
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    // Expected value of following the policy for one step in a state.
+    fn backup(&self, distributions: &[Vec<(ActionBox, f64)>], state: usize, values: &[f64]) -> f64 {
+        distributions[state]
+            .iter()
+            .map(|(action, p)| {
+                p * expected_value(&self.mdp.actions(state)[action], values, self.discount)
+            })
+            .sum()
+    }
+
+    fn residual(&self, distributions: &[Vec<(ActionBox, f64)>], values: &[f64]) -> f64 {
+        (0..values.len())
+            .map(|i| {
+                let backup = self.backup(distributions, i, values);
+                if backup == values[i] {
+                    0.0
+                } else {
+                    (backup - values[i]).abs()
+                }
+            })
+            .fold(0.0, f64::max)
+    }
+}
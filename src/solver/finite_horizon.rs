@@ -0,0 +1,89 @@
+use std::time::Instant;
+
+use crate::{mdp::Mdp, model::State, policy::Policy};
+
+use super::{best_value, greedy_policy, Solution, Solver, SolverError};
+
+/// Backward induction over a fixed number of steps.
+///
+/// The horizon lives in the solver rather than in the state, so the `Mdp` doesn't need a
+/// turn counter. The result is non-stationary: there is one `Policy` for every time step, and
+/// values are indexed by `(t, state)`, where `t` is the number of steps already taken.
+///
+/// As a `Solver` it reports the values and policy at `t = 0`. The values are exact, so the
+/// residual is always 0, and `SolverConfig` does not apply.
+pub struct FiniteHorizonSolver<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    horizon: usize,
+    values: Vec<Vec<f64>>,
+    policies: Vec<Policy>,
+}
+
+impl<'a, S: State> FiniteHorizonSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64, horizon: usize) -> Self {
+        Self {
+            mdp,
+            discount,
+            horizon,
+            values: vec![],
+            policies: vec![],
+        }
+    }
+
+    pub fn horizon(&self) -> usize {
+        self.horizon
+    }
+
+    /// `values()[t][state]` for `t` in `0..=horizon`. Everything is 0 at the horizon.
+    pub fn values(&self) -> &[Vec<f64>] {
+        self.values.as_ref()
+    }
+
+    pub fn value(&self, t: usize, state: usize) -> f64 {
+        self.values[t][state]
+    }
+
+    /// One policy per time step, for `t` in `0..horizon`.
+    pub fn policies(&self) -> &[Policy] {
+        self.policies.as_ref()
+    }
+
+    pub fn policy(&self, t: usize) -> &Policy {
+        &self.policies[t]
+    }
+}
+
+impl<'a, S: State> Solver<S> for FiniteHorizonSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let n = self.mdp.states().len();
+
+        let mut values = vec![vec![0.0; n]; self.horizon + 1];
+        let mut policies = Vec::with_capacity(self.horizon);
+        for t in (0..self.horizon).rev() {
+            let next = &values[t + 1];
+            let current = (0..n)
+                .map(|i| best_value(self.mdp, i, next, self.discount))
+                .collect();
+            policies.push(greedy_policy(self.mdp, next, self.discount));
+            values[t] = current;
+        }
+        policies.reverse();
+
+        self.values = values;
+        self.policies = policies;
+
+        let (values, policy) = match self.policies.first() {
+            Some(policy) => (self.values[0].clone(), policy.clone()),
+            None => (vec![0.0; n], Policy::new(vec![None; n])),
+        };
+        Ok(Solution::new(
+            values,
+            policy,
+            self.horizon,
+            0.0,
+            start.elapsed(),
+        ))
+    }
+}
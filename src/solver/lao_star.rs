@@ -0,0 +1,260 @@
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    mdp::Transition,
+    model::{Action, ActionBox, State},
+    policy::Policy,
+};
+
+use super::{expected_value, Solution, Solver, SolverConfig, SolverError};
+
+/// Heuristic search that expands the model on demand (Hansen and Zilberstein's ILAO*).
+///
+/// Instead of an enumerated `Mdp` it takes the initial state and the grounded actions (see
+/// `MdpBuilder::actions`), and only generates the successors of states that end up in the
+/// best partial solution graph, which is everything the current greedy policy can reach from
+/// the initial state. States at its fringe that haven't been expanded yet are valued by an
+/// admissible heuristic, which must never underestimate the optimal value (we are maximising).
+///
+/// Every iteration walks the solution graph depth first, expands its fringe states and backs
+/// up the rest in post-order. It is done once the solution graph has no fringe left and the
+/// largest change in an iteration is within the tolerance.
+///
+/// States are numbered in the order they are generated, with the initial state at 0, and the
+/// `Solution` is indexed the same way (see `states()`). Fringe states keep their heuristic
+/// value, and only states in the solution graph get an action.
+pub struct LaoStarSolver<'a, S: State> {
+    actions: Vec<Action<S>>,
+    discount: f64,
+    heuristic: Box<dyn Fn(&S) -> f64 + 'a>,
+    states: Vec<S>,
+    indices: HashMap<u64, usize>,
+    // `None` until the state has been expanded.
+    transitions: Vec<Option<HashMap<ActionBox, Vec<Transition>>>>,
+    values: Vec<f64>,
+    best: Vec<Option<ActionBox>>,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> LaoStarSolver<'a, S> {
+    pub fn new(
+        initial: S,
+        actions: Vec<Action<S>>,
+        discount: f64,
+        heuristic: impl Fn(&S) -> f64 + 'a,
+    ) -> Self {
+        let mut solver = Self {
+            actions,
+            discount,
+            heuristic: Box::new(heuristic),
+            states: vec![],
+            indices: HashMap::new(),
+            transitions: vec![],
+            values: vec![],
+            best: vec![],
+            config: SolverConfig::default(),
+        };
+        solver.index_or_insert(initial);
+        solver
+    }
+
+    /// Only the iteration limit, timeout and tolerances apply.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Every state generated so far, expanded or not.
+    pub fn states(&self) -> &[S] {
+        self.states.as_ref()
+    }
+
+    pub fn index_of_state(&self, state: &S) -> Option<usize> {
+        self.indices.get(&state.get_hash()).copied()
+    }
+
+    pub fn is_expanded(&self, state: usize) -> bool {
+        self.transitions[state].is_some()
+    }
+
+    pub fn expanded_states(&self) -> usize {
+        self.transitions.iter().filter(|t| t.is_some()).count()
+    }
+
+    /// The actions and transitions of an expanded state.
+    pub fn actions(&self, state: usize) -> Option<&HashMap<ActionBox, Vec<Transition>>> {
+        self.transitions[state].as_ref()
+    }
+
+    /// The states the best policy can reach from the initial state, in depth first order.
+    pub fn solution_graph(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.states.len()];
+        seen[0] = true;
+        let mut stack = vec![0];
+        let mut graph = vec![];
+        while let Some(state) = stack.pop() {
+            graph.push(state);
+            for t in self.best_transitions(state) {
+                if !seen[t.to()] {
+                    seen[t.to()] = true;
+                    stack.push(t.to());
+                }
+            }
+        }
+        graph
+    }
+
+    fn index_or_insert(&mut self, state: S) -> usize {
+        let hash = state.get_hash();
+        if let Some(&index) = self.indices.get(&hash) {
+            return index;
+        }
+        let index = self.states.len();
+        self.values.push((self.heuristic)(&state));
+        self.states.push(state);
+        self.indices.insert(hash, index);
+        self.transitions.push(None);
+        self.best.push(None);
+        index
+    }
+
+    fn expand(&mut self, from: usize) {
+        let mut transitions: HashMap<ActionBox, Vec<Transition>> = HashMap::new();
+        for i in 0..self.actions.len() {
+            if !self.actions[i].preconditions_valid(&self.states[from]) {
+                continue;
+            }
+            let action = self.actions[i].action();
+            for result in self.actions[i].get_successor_states(&self.states[from]) {
+                let to = self.index_or_insert(result.state);
+                transitions.entry(action.clone()).or_default().push(
+                    Transition::new(from, to, result.reward, result.probability, action.clone())
+                        .with_rewards(result.rewards)
+                        .with_costs(result.costs),
+                );
+            }
+        }
+        self.transitions[from] = Some(transitions);
+    }
+
+    fn best_transitions(&self, state: usize) -> &[Transition] {
+        match (&self.transitions[state], &self.best[state]) {
+            (Some(transitions), Some(action)) => &transitions[action],
+            _ => &[],
+        }
+    }
+
+    // Updates the value and best action of an expanded state, returning how much it changed.
+    fn backup(&mut self, state: usize) -> f64 {
+        let best = self.transitions[state].as_ref().and_then(|transitions| {
+            let mut transitions = transitions.iter().collect::<Vec<_>>();
+            transitions.sort_by_key(|(action, _)| action.sort_key());
+            transitions
+                .into_iter()
+                .map(|(action, transitions)| {
+                    (
+                        expected_value(transitions, &self.values, self.discount),
+                        action,
+                    )
+                })
+                .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+        });
+        let (value, action) = match best {
+            Some((value, action)) => (value, Some(action.clone())),
+            None => (0.0, None),
+        };
+        let change = (value - self.values[state]).abs();
+        self.values[state] = value;
+        self.best[state] = action;
+        change
+    }
+
+    // One depth first pass over the solution graph. Fringe states are expanded but not
+    // followed, everything is backed up in post-order. Returns whether anything was expanded
+    // and the largest change in value.
+    fn iterate(&mut self) -> (bool, f64) {
+        let mut expanded = false;
+        let mut residual: f64 = 0.0;
+        let mut seen = vec![false; self.states.len()];
+        seen[0] = true;
+        // The state, and whether its successors have been pushed yet.
+        let mut stack = vec![(0, false)];
+        while let Some((state, visited)) = stack.pop() {
+            if !self.is_expanded(state) {
+                self.expand(state);
+                seen.resize(self.states.len(), false);
+                expanded = true;
+            } else if !visited {
+                stack.push((state, true));
+                for t in self.best_transitions(state) {
+                    if !seen[t.to()] {
+                        seen[t.to()] = true;
+                        stack.push((t.to(), false));
+                    }
+                }
+                continue;
+            }
+            residual = residual.max(self.backup(state));
+        }
+        (expanded, residual)
+    }
+
+    fn solution(&self, iterations: usize, residual: f64, start: Instant) -> Solution {
+        let mut actions = vec![None; self.states.len()];
+        for state in self.solution_graph() {
+            actions[state] = self.best[state].clone();
+        }
+        Solution::new(
+            self.values.clone(),
+            Policy::new(actions),
+            iterations,
+            residual,
+            start.elapsed(),
+        )
+    }
+}
+
+impl<'a, S: State> Solver<S> for LaoStarSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let mut iterations = 0;
+        loop {
+            let (expanded, residual) = self.iterate();
+            iterations += 1;
+
+            if self
+                .values
+                .iter()
+                .any(|v| v.is_nan() || *v == f64::INFINITY)
+            {
+                return Err(SolverError::Diverged { iterations });
+            }
+            // Backups can switch the best action towards a state that isn't expanded yet,
+            // so the solution graph has to be checked again after the pass.
+            let complete = !expanded
+                && self
+                    .solution_graph()
+                    .into_iter()
+                    .all(|state| self.is_expanded(state));
+            if complete && residual < self.config.tolerance(self.values[0]) {
+                // The pass only backs up what it visits, so a switch towards a state it didn't
+                // visit leaves a stale value in the graph. Check the whole graph once more.
+                let residual = self
+                    .solution_graph()
+                    .into_iter()
+                    .map(|state| self.backup(state))
+                    .fold(0.0, f64::max);
+                let complete = self
+                    .solution_graph()
+                    .into_iter()
+                    .all(|state| self.is_expanded(state));
+                if complete && residual < self.config.tolerance(self.values[0]) {
+                    return Ok(self.solution(iterations, residual, start));
+                }
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                return Err(error(self.solution(iterations, residual, start)));
+            }
+        }
+    }
+}
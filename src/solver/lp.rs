@@ -0,0 +1,116 @@
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    lp::{LinearProgram, LpError, Relation},
+    mdp::Mdp,
+    model::{ActionBox, State},
+};
+
+use super::{bellman_residual, greedy_policy, Solution, Solver, SolverError};
+
+/// Exact solver for discounted MDPs using linear programming.
+///
+/// The primal LP minimises `sum_s weight(s) * v(s)` subject to
+/// `v(s) >= r(s, a) + discount * sum_s' p(s' | s, a) * v(s')` for every state and action.
+/// The simplex runs on its dual, which is already in standard form: the variables are the
+/// discounted occupancy measures `x(s, a)` and the state values come back as its shadow prices.
+///
+/// `Solution::iterations` is the number of simplex pivots. `SolverConfig` does not apply.
+pub struct LpSolver<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    weights: Vec<f64>,
+    occupancy: Vec<HashMap<ActionBox, f64>>,
+}
+
+impl<'a, S: State> LpSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        assert!(
+            (0.0..1.0).contains(&discount),
+            "the LP solver needs a discount in [0, 1)"
+        );
+        let n = mdp.states().len();
+        Self {
+            mdp,
+            discount,
+            weights: vec![1.0 / n as f64; n],
+            occupancy: vec![],
+        }
+    }
+
+    /// Where the occupancy measures start from. Defaults to uniform over all states.
+    /// States with no weight (and nothing leading into them) may not get optimal values.
+    pub fn initial_distribution(mut self, weights: Vec<f64>) -> Self {
+        assert_eq!(weights.len(), self.mdp.states().len());
+        self.weights = weights;
+        self
+    }
+
+    /// Expected discounted number of times each action is taken in each state, under the
+    /// optimal policy and starting from the initial distribution. Empty until solved.
+    pub fn occupancy(&self) -> &[HashMap<ActionBox, f64>] {
+        self.occupancy.as_ref()
+    }
+}
+
+impl<'a, S: State> Solver<S> for LpSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let n = self.mdp.states().len();
+
+        // One variable per (state, action). States without actions get a zero reward self
+        // loop instead, so they can still absorb occupancy.
+        let mut variables = vec![];
+        for state in 0..n {
+            let actions = self.mdp.actions(state);
+            if actions.is_empty() {
+                variables.push((state, None));
+            }
+            for action in actions.keys() {
+                variables.push((state, Some(action)));
+            }
+        }
+
+        let mut rewards = vec![0.0; variables.len()];
+        let mut flow = vec![vec![0.0; variables.len()]; n];
+        for (column, &(state, action)) in variables.iter().enumerate() {
+            flow[state][column] += 1.0;
+            let Some(action) = action else {
+                flow[state][column] -= self.discount;
+                continue;
+            };
+            for t in self.mdp.actions(state)[action].iter() {
+                rewards[column] += t.probability() * t.reward();
+                flow[t.to()][column] -= self.discount * t.probability();
+            }
+        }
+
+        let lp = flow
+            .into_iter()
+            .zip(self.weights.iter())
+            .fold(LinearProgram::maximize(rewards), |lp, (row, &weight)| {
+                lp.constraint(row, Relation::Equal, weight)
+            });
+        let lp_solution = match lp.solve() {
+            Ok(solution) => solution,
+            Err(LpError::Unbounded) => return Err(SolverError::Diverged { iterations: 0 }),
+            Err(LpError::Infeasible) => panic!("the occupancy LP of a discounted MDP is feasible"),
+        };
+
+        self.occupancy = vec![HashMap::new(); n];
+        for (&(state, action), &x) in variables.iter().zip(lp_solution.variables()) {
+            if let Some(action) = action {
+                self.occupancy[state].insert(action.clone(), x);
+            }
+        }
+
+        let values = lp_solution.duals().to_vec();
+        Ok(Solution::new(
+            values.clone(),
+            greedy_policy(self.mdp, &values, self.discount),
+            lp_solution.pivots(),
+            bellman_residual(self.mdp, &values, self.discount),
+            start.elapsed(),
+        ))
+    }
+}
@@ -0,0 +1,137 @@
+use std::{cmp::Ordering, collections::BinaryHeap, time::Instant};
+
+use crate::{mdp::Mdp, model::State};
+
+use super::{
+    bellman_residual, best_value, greedy_policy, Solution, Solver, SolverConfig, SolverError,
+};
+
+// A state waiting in the queue, ordered by its Bellman residual.
+struct Entry(f64, usize);
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+/// Prioritized sweeping value iteration.
+///
+/// Instead of sweeping every state, it always backs up the state with the largest Bellman
+/// residual. When a state's value changes, the residuals of its predecessors are recomputed
+/// and they are queued again. It stops once no residual is above the tolerance.
+///
+/// `Solution::iterations` counts backups in units of full sweeps (rounded up), so it can be
+/// compared with `ValueIterationSolver`. The iteration limit is measured the same way.
+pub struct PrioritizedSweepingSolver<'a, S: State> {
+    values: Vec<f64>,
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> PrioritizedSweepingSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        Self {
+            values: vec![0.0; mdp.states().len()],
+            mdp,
+            discount,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// The stopping rule is ignored, states are compared one at a time.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    // The residual of a state, or 0 if it is within the tolerance.
+    fn priority(&self, state: usize) -> f64 {
+        let backup = best_value(self.mdp, state, &self.values, self.discount);
+        let residual = if backup == self.values[state] {
+            0.0
+        } else {
+            (backup - self.values[state]).abs()
+        };
+        if residual < self.config.tolerance(backup) {
+            0.0
+        } else {
+            residual
+        }
+    }
+
+    fn solution(&self, iterations: usize, start: Instant) -> Solution {
+        Solution::new(
+            self.values.clone(),
+            greedy_policy(self.mdp, &self.values, self.discount),
+            iterations,
+            bellman_residual(self.mdp, &self.values, self.discount),
+            start.elapsed(),
+        )
+    }
+}
+
+impl<'a, S: State> Solver<S> for PrioritizedSweepingSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let n = self.mdp.states().len();
+        let predecessors = self.mdp.predecessors();
+
+        let mut priorities = (0..n).map(|i| self.priority(i)).collect::<Vec<_>>();
+        let mut queue = priorities
+            .iter()
+            .enumerate()
+            .filter(|(_, &p)| p > 0.0)
+            .map(|(i, &p)| Entry(p, i))
+            .collect::<BinaryHeap<_>>();
+
+        let mut backups: usize = 0;
+        while let Some(Entry(priority, state)) = queue.pop() {
+            // Skip entries that were superseded by a later push.
+            if priority != priorities[state] {
+                continue;
+            }
+
+            self.values[state] = best_value(self.mdp, state, &self.values, self.discount);
+            priorities[state] = 0.0;
+            backups += 1;
+
+            if !self.values[state].is_finite() {
+                return Err(SolverError::Diverged {
+                    iterations: backups.div_ceil(n),
+                });
+            }
+
+            for &previous in predecessors[state].iter() {
+                let priority = self.priority(previous);
+                if priority > priorities[previous] {
+                    priorities[previous] = priority;
+                    queue.push(Entry(priority, previous));
+                }
+            }
+
+            if backups.is_multiple_of(n) {
+                if let Some(error) = self.config.exceeded(backups / n, start) {
+                    return Err(error(self.solution(backups / n, start)));
+                }
+            }
+        }
+
+        Ok(self.solution(backups.div_ceil(n), start))
+    }
+}
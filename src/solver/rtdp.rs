@@ -0,0 +1,305 @@
+use std::time::Instant;
+
+use crate::{
+    mdp::{Mdp, Transition},
+    model::{ActionBox, State},
+    policy::Policy,
+    rng::Rng,
+};
+
+use super::{Solution, Solver, SolverConfig, SolverError};
+
+const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+// The parts shared by RTDP and LRTDP: lazily initialised values, greedy backups and sampling.
+struct TrialSearch<'a, S: State> {
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    heuristic: Box<dyn Fn(&S) -> f64 + 'a>,
+    values: Vec<Option<f64>>,
+    solved: Vec<bool>,
+    rng: Rng,
+    max_depth: usize,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> TrialSearch<'a, S> {
+    fn new(mdp: &'a Mdp<S>, discount: f64, heuristic: Box<dyn Fn(&S) -> f64 + 'a>) -> Self {
+        let n = mdp.states().len();
+        Self {
+            mdp,
+            discount,
+            heuristic,
+            values: vec![None; n],
+            solved: vec![false; n],
+            rng: Rng::new(0),
+            max_depth: DEFAULT_MAX_DEPTH,
+            config: SolverConfig::default(),
+        }
+    }
+
+    fn is_terminal(&self, state: usize) -> bool {
+        self.mdp.actions(state).is_empty()
+    }
+
+    fn value(&self, state: usize) -> f64 {
+        if self.is_terminal(state) {
+            return 0.0;
+        }
+        self.values[state].unwrap_or_else(|| (self.heuristic)(&self.mdp.states()[state]))
+    }
+
+    fn q_value(&self, transitions: &[Transition]) -> f64 {
+        transitions
+            .iter()
+            .map(|t| t.probability() * (t.reward() + self.discount * self.value(t.to())))
+            .sum()
+    }
+
+    fn greedy(&self, state: usize) -> Option<(f64, &'a ActionBox, &'a [Transition])> {
+        let mdp = self.mdp;
+        mdp.sorted_actions(state)
+            .into_iter()
+            .map(|(action, transitions)| {
+                (self.q_value(transitions), action, transitions.as_slice())
+            })
+            .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+    }
+
+    fn residual(&self, state: usize) -> f64 {
+        match self.greedy(state) {
+            Some((best, _, _)) => (best - self.value(state)).abs(),
+            None => 0.0,
+        }
+    }
+
+    fn update(&mut self, state: usize) {
+        if let Some((best, _, _)) = self.greedy(state) {
+            self.values[state] = Some(best);
+        }
+    }
+
+    fn sample(&mut self, transitions: &[Transition]) -> usize {
+        let mut remaining =
+            self.rng.next_f64() * transitions.iter().map(|t| t.probability()).sum::<f64>();
+        for t in transitions {
+            remaining -= t.probability();
+            if remaining < 0.0 {
+                return t.to();
+            }
+        }
+        transitions.last().map(|t| t.to()).unwrap()
+    }
+
+    // Follows the greedy policy from the initial state, backing up every state on the way,
+    // until it reaches a terminal or solved state. Returns the states it visited.
+    fn trial(&mut self) -> Vec<usize> {
+        let mut visited = vec![];
+        let mut state = 0;
+        while !self.solved[state] && !self.is_terminal(state) && visited.len() < self.max_depth {
+            visited.push(state);
+            self.update(state);
+            let Some((_, _, transitions)) = self.greedy(state) else {
+                break;
+            };
+            state = self.sample(transitions);
+        }
+        visited
+    }
+
+    // The states the greedy policy can reach from `state`, stopping at solved states.
+    // Returns them together with whether all of them are within the tolerance.
+    fn greedy_envelope(&self, state: usize) -> (Vec<usize>, bool) {
+        let mut converged = true;
+        let mut open = vec![state];
+        let mut seen = vec![false; self.mdp.states().len()];
+        seen[state] = true;
+        let mut closed = vec![];
+        while let Some(s) = open.pop() {
+            closed.push(s);
+            if self.residual(s) >= self.config.tolerance(self.value(s)) {
+                converged = false;
+                continue;
+            }
+            if let Some((_, _, transitions)) = self.greedy(s) {
+                for t in transitions {
+                    if !self.solved[t.to()] && !seen[t.to()] {
+                        seen[t.to()] = true;
+                        open.push(t.to());
+                    }
+                }
+            }
+        }
+        (closed, converged)
+    }
+
+    // Labels everything reachable from `state` as solved if it has converged, otherwise backs
+    // those states up again.
+    fn check_solved(&mut self, state: usize) -> bool {
+        if self.solved[state] {
+            return true;
+        }
+        let (closed, converged) = self.greedy_envelope(state);
+        if converged {
+            for s in closed {
+                self.solved[s] = true;
+            }
+        } else {
+            for s in closed.into_iter().rev() {
+                self.update(s);
+            }
+        }
+        converged
+    }
+
+    fn solution(&self, trials: usize, start: Instant) -> Solution {
+        let n = self.mdp.states().len();
+        let values = (0..n).map(|i| self.value(i)).collect();
+        let actions = (0..n)
+            .map(|i| {
+                self.values[i]?;
+                self.greedy(i).map(|(_, action, _)| action.clone())
+            })
+            .collect();
+        let (envelope, _) = self.greedy_envelope(0);
+        let residual = envelope
+            .into_iter()
+            .map(|s| self.residual(s))
+            .fold(0.0, f64::max);
+        Solution::new(
+            values,
+            Policy::new(actions),
+            trials,
+            residual,
+            start.elapsed(),
+        )
+    }
+}
+
+/// Real-time dynamic programming.
+///
+/// Runs greedy trials from the initial state (state 0), sampling outcomes and backing up the
+/// states it passes through. Values start from an admissible heuristic, which must never
+/// underestimate the optimal value (we are maximising). Only the states the trials touch are
+/// ever backed up, so on models with a known start most of the `Mdp` is never looked at.
+///
+/// It stops once every state the greedy policy can reach from the start is within the
+/// tolerance. Rarely sampled states converge slowly, so on long horizons this can take a lot
+/// of trials, see `LrtdpSolver`. The policy only covers states that were backed up, the rest have no action
+/// and keep their heuristic value. `Solution::iterations` counts trials.
+pub struct RtdpSolver<'a, S: State> {
+    search: TrialSearch<'a, S>,
+}
+
+/// Labeled RTDP.
+///
+/// Like `RtdpSolver`, but after every trial it labels states whose whole greedy envelope has
+/// converged as solved. Trials stop as soon as they hit a solved state, and the search is done
+/// once the initial state is solved, which usually takes far fewer trials.
+pub struct LrtdpSolver<'a, S: State> {
+    search: TrialSearch<'a, S>,
+}
+
+impl<'a, S: State> RtdpSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64, heuristic: impl Fn(&S) -> f64 + 'a) -> Self {
+        Self {
+            search: TrialSearch::new(mdp, discount, Box::new(heuristic)),
+        }
+    }
+
+    /// The iteration limit counts trials.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.search.config = config;
+        self
+    }
+
+    /// Seed for sampling outcomes during trials.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.search.rng = Rng::new(seed);
+        self
+    }
+
+    /// Longest a single trial may run before it is cut off.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.search.max_depth = max_depth;
+        self
+    }
+
+    /// Number of states that have been backed up at least once.
+    pub fn visited_states(&self) -> usize {
+        self.search.values.iter().filter(|v| v.is_some()).count()
+    }
+}
+
+impl<'a, S: State> LrtdpSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64, heuristic: impl Fn(&S) -> f64 + 'a) -> Self {
+        Self {
+            search: TrialSearch::new(mdp, discount, Box::new(heuristic)),
+        }
+    }
+
+    /// The iteration limit counts trials.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.search.config = config;
+        self
+    }
+
+    /// Seed for sampling outcomes during trials.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.search.rng = Rng::new(seed);
+        self
+    }
+
+    /// Longest a single trial may run before it is cut off.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.search.max_depth = max_depth;
+        self
+    }
+
+    /// Number of states that have been backed up at least once.
+    pub fn visited_states(&self) -> usize {
+        self.search.values.iter().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_solved(&self, state: usize) -> bool {
+        self.search.solved[state]
+    }
+}
+
+impl<'a, S: State> Solver<S> for RtdpSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let mut trials = 0;
+        loop {
+            self.search.trial();
+            trials += 1;
+            if self.search.greedy_envelope(0).1 {
+                return Ok(self.search.solution(trials, start));
+            }
+            if let Some(error) = self.search.config.exceeded(trials, start) {
+                return Err(error(self.search.solution(trials, start)));
+            }
+        }
+    }
+}
+
+impl<'a, S: State> Solver<S> for LrtdpSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let mut trials = 0;
+        while !self.search.check_solved(0) {
+            let mut visited = self.search.trial();
+            trials += 1;
+            while let Some(state) = visited.pop() {
+                if !self.search.check_solved(state) {
+                    break;
+                }
+            }
+            if let Some(error) = self.search.config.exceeded(trials, start) {
+                return Err(error(self.search.solution(trials, start)));
+            }
+        }
+
+        Ok(self.search.solution(trials, start))
+    }
+}
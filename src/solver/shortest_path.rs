@@ -0,0 +1,270 @@
+use std::time::Instant;
+
+use crate::{
+    mdp::{Mdp, Transition},
+    model::{ActionBox, State},
+    policy::Policy,
+};
+
+use super::{evaluate_policy, expected_value, Solution, Solver, SolverConfig, SolverError};
+
+/// Stochastic shortest path solver.
+///
+/// Rewards are read as negative costs, there is no discounting, and the task ends in any state
+/// matching the goal predicate (goal states cost nothing and their actions are ignored).
+///
+/// Before iterating, it works out which states can reach a goal with probability 1 under some
+/// policy. Every other state has an infinite expected cost, so it gets no action, a value of
+/// negative infinity and a cost-to-go of `None`, instead of value iteration chasing it
+/// forever. The remaining states only consider actions that keep them inside that set.
+///
+/// Value iteration starts from the values of a policy that is guaranteed to reach a goal, so
+/// loops that cost nothing can't pass as optimal. When such a loop ties with the best action,
+/// the policy prefers actions that make progress towards a goal, so it stays proper.
+pub struct StochasticShortestPathSolver<'a, S: State> {
+    values: Vec<f64>,
+    mdp: &'a Mdp<S>,
+    goals: Vec<bool>,
+    solvable: Vec<bool>,
+    // How many steps of the almost sure attractor a state is away from a goal.
+    rank: Vec<usize>,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> StochasticShortestPathSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, is_goal: impl Fn(&S) -> bool) -> Self {
+        let n = mdp.states().len();
+        let goals = mdp.states().iter().map(is_goal).collect();
+        let mut solver = Self {
+            values: vec![0.0; n],
+            mdp,
+            goals,
+            solvable: vec![true; n],
+            rank: vec![0; n],
+            config: SolverConfig::default(),
+        };
+        solver.find_solvable_states();
+        solver
+    }
+
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn is_goal(&self, state: usize) -> bool {
+        self.goals[state]
+    }
+
+    /// Whether some policy reaches a goal from this state with probability 1.
+    pub fn is_solvable(&self, state: usize) -> bool {
+        self.solvable[state]
+    }
+
+    /// Expected total cost to reach a goal, or `None` if it is infinite.
+    pub fn costs_to_go(&self) -> Vec<Option<f64>> {
+        self.values
+            .iter()
+            .zip(self.solvable.iter())
+            .map(|(value, &solvable)| if solvable { Some(0.0 - value) } else { None })
+            .collect()
+    }
+
+    /// The states from which the policy is not guaranteed to reach a goal.
+    pub fn improper_states(&self, policy: &Policy) -> Vec<usize> {
+        let n = self.mdp.states().len();
+        let successors = |state: usize| -> &[Transition] {
+            match policy.get_action(state) {
+                Some(action) if !self.goals[state] => &self.mdp.actions(state)[action],
+                _ => &[],
+            }
+        };
+
+        // A state is proper iff it can't reach a state that never reaches a goal.
+        let mut reaches_goal = self.goals.clone();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..n {
+                if !reaches_goal[i] && successors(i).iter().any(|t| reaches_goal[t.to()]) {
+                    reaches_goal[i] = true;
+                    changed = true;
+                }
+            }
+        }
+        let mut improper = reaches_goal.iter().map(|r| !r).collect::<Vec<_>>();
+        changed = true;
+        while changed {
+            changed = false;
+            for i in 0..n {
+                if !improper[i] && successors(i).iter().any(|t| improper[t.to()]) {
+                    improper[i] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        (0..n).filter(|&i| improper[i]).collect()
+    }
+
+    // Actions that can't leave the set of solvable states.
+    fn allowed(
+        &self,
+        state: usize,
+    ) -> impl Iterator<Item = (&'a ActionBox, &'a Vec<Transition>)> + '_ {
+        let mdp = self.mdp;
+        mdp.sorted_actions(state)
+            .into_iter()
+            .filter(|(_, transitions)| transitions.iter().all(|t| self.solvable[t.to()]))
+    }
+
+    // Greatest fixpoint of the states that have an action staying inside the set and moving
+    // closer to a goal. Also records how many steps each state is from a goal.
+    fn find_solvable_states(&mut self) {
+        let n = self.mdp.states().len();
+        loop {
+            let mut rank = (0..n)
+                .map(|i| if self.goals[i] { 0 } else { usize::MAX })
+                .collect::<Vec<_>>();
+            for round in 1.. {
+                let reached = (0..n)
+                    .filter(|&i| self.solvable[i] && rank[i] == usize::MAX)
+                    .filter(|&i| {
+                        self.allowed(i).any(|(_, transitions)| {
+                            transitions.iter().any(|t| rank[t.to()] < round)
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                if reached.is_empty() {
+                    break;
+                }
+                for i in reached {
+                    rank[i] = round;
+                }
+            }
+
+            let solvable = rank.iter().map(|&r| r != usize::MAX).collect::<Vec<_>>();
+            self.rank = rank;
+            if solvable == self.solvable {
+                return;
+            }
+            self.solvable = solvable;
+        }
+    }
+
+    fn backup(&self, state: usize) -> f64 {
+        if self.goals[state] {
+            return 0.0;
+        }
+        if !self.solvable[state] {
+            return f64::NEG_INFINITY;
+        }
+        self.allowed(state)
+            .map(|(_, transitions)| expected_value(transitions, &self.values, 1.0))
+            .fold(f64::NEG_INFINITY, f64::max)
+    }
+
+    fn progresses(&self, state: usize, transitions: &[Transition]) -> bool {
+        transitions
+            .iter()
+            .any(|t| self.rank[t.to()] < self.rank[state])
+    }
+
+    fn policy(&self) -> Policy {
+        let n = self.mdp.states().len();
+        let tolerance = self.config.absolute_tolerance;
+        let mut actions = (0..n)
+            .map(|i| {
+                if self.goals[i] || !self.solvable[i] {
+                    return None;
+                }
+                let best = self.backup(i);
+                self.allowed(i)
+                    .map(|(action, transitions)| {
+                        let value = expected_value(transitions, &self.values, 1.0);
+                        let near_best = value >= best - tolerance;
+                        ((near_best, self.progresses(i, transitions), value), action)
+                    })
+                    .reduce(|accum, item| if accum.0 >= item.0 { accum } else { item })
+                    .map(|(_, action)| action.clone())
+            })
+            .collect::<Vec<_>>();
+
+        // Fall back to making progress wherever the policy could still loop forever.
+        for i in self.improper_states(&Policy::new(actions.clone())) {
+            if self.solvable[i] && !self.goals[i] {
+                actions[i] = self
+                    .allowed(i)
+                    .find(|(_, transitions)| self.progresses(i, transitions))
+                    .map(|(action, _)| action.clone());
+            }
+        }
+
+        Policy::new(actions)
+    }
+
+    fn residual(&self) -> f64 {
+        (0..self.mdp.states().len())
+            .filter(|&i| self.solvable[i])
+            .map(|i| (self.backup(i) - self.values[i]).abs())
+            .fold(0.0, f64::max)
+    }
+
+    fn solution(&self, iterations: usize, start: Instant) -> Solution {
+        Solution::new(
+            self.values.clone(),
+            self.policy(),
+            iterations,
+            self.residual(),
+            start.elapsed(),
+        )
+    }
+}
+
+impl<'a, S: State> Solver<S> for StochasticShortestPathSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let n = self.mdp.states().len();
+
+        // Start from the values of a proper policy. Iterating upwards from a pessimistic
+        // start can't get stuck on a free loop that never reaches the goal.
+        let attractor = (0..n)
+            .map(|i| {
+                if self.goals[i] {
+                    return None;
+                }
+                self.allowed(i)
+                    .find(|(_, transitions)| self.progresses(i, transitions))
+                    .map(|(action, _)| action.clone())
+            })
+            .collect::<Vec<_>>();
+        self.values = evaluate_policy(self.mdp, &attractor, 1.0);
+        for i in 0..n {
+            if !self.solvable[i] {
+                self.values[i] = f64::NEG_INFINITY;
+            }
+        }
+
+        // Only the solvable states take part in the convergence checks.
+        let states = (0..n).filter(|&i| self.solvable[i]).collect::<Vec<_>>();
+        let mut iterations = 0;
+        loop {
+            let old_values = states.iter().map(|&i| self.values[i]).collect::<Vec<_>>();
+            for &i in states.iter() {
+                self.values[i] = self.backup(i);
+            }
+            iterations += 1;
+
+            let values = states.iter().map(|&i| self.values[i]).collect::<Vec<_>>();
+            if self.config.converged(&old_values, &values) {
+                return Ok(self.solution(iterations, start));
+            }
+            if self.config.diverged(&old_values, &values, 1.0) {
+                return Err(SolverError::Diverged { iterations });
+            }
+            if let Some(error) = self.config.exceeded(iterations, start) {
+                return Err(error(self.solution(iterations, start)));
+            }
+        }
+    }
+}
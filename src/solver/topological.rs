@@ -0,0 +1,124 @@
+use std::time::Instant;
+
+use crate::{graph::strongly_connected_components, mdp::Mdp, model::State};
+
+use super::{
+    bellman_residual, best_value, greedy_policy, Solution, Solver, SolverConfig, SolverError,
+};
+
+/// Topological value iteration.
+///
+/// Splits the transition graph into strongly connected components and solves them one at a
+/// time, starting with the ones nothing else depends on. Value iteration only runs inside a
+/// component, and a component without any cycle is solved with a single backup.
+///
+/// `Solution::iterations` is the largest number of sweeps any one component needed.
+pub struct TopologicalSolver<'a, S: State> {
+    values: Vec<f64>,
+    mdp: &'a Mdp<S>,
+    discount: f64,
+    config: SolverConfig,
+}
+
+impl<'a, S: State> TopologicalSolver<'a, S> {
+    pub fn new(mdp: &'a Mdp<S>, discount: f64) -> Self {
+        Self {
+            values: vec![0.0; mdp.states().len()],
+            mdp,
+            discount,
+            config: SolverConfig::default(),
+        }
+    }
+
+    /// The tolerances and iteration limit apply to each component separately.
+    pub fn with_config(mut self, config: SolverConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn is_acyclic(&self, component: &[usize]) -> bool {
+        let state = component[0];
+        component.len() == 1
+            && self
+                .mdp
+                .actions(state)
+                .values()
+                .flatten()
+                .all(|t| t.to() != state)
+    }
+
+    fn solution(&self, iterations: usize, start: Instant) -> Solution {
+        Solution::new(
+            self.values.clone(),
+            greedy_policy(self.mdp, &self.values, self.discount),
+            iterations,
+            bellman_residual(self.mdp, &self.values, self.discount),
+            start.elapsed(),
+        )
+    }
+}
+
+impl<'a, S: State> Solver<S> for TopologicalSolver<'a, S> {
+    fn solve(&mut self) -> Result<Solution, SolverError> {
+        let start = Instant::now();
+        let mut iterations = 0;
+
+        // Components come out with everything they lead to already solved.
+        let successors = self.mdp.successors();
+        let mut in_component = vec![false; successors.len()];
+        for component in strongly_connected_components(&successors) {
+            if self.is_acyclic(&component) {
+                let state = component[0];
+                self.values[state] = best_value(self.mdp, state, &self.values, self.discount);
+                iterations = usize::max(iterations, 1);
+                continue;
+            }
+
+            for &i in component.iter() {
+                in_component[i] = true;
+            }
+            let closed = component
+                .iter()
+                .all(|&i| successors[i].iter().all(|&j| in_component[j]));
+            for &i in component.iter() {
+                in_component[i] = false;
+            }
+
+            let mut sweeps = 0;
+            loop {
+                let old_values = component
+                    .iter()
+                    .map(|&i| self.values[i])
+                    .collect::<Vec<_>>();
+                for &i in component.iter() {
+                    self.values[i] = best_value(self.mdp, i, &self.values, self.discount);
+                }
+                sweeps += 1;
+                iterations = usize::max(iterations, sweeps);
+
+                let values = component
+                    .iter()
+                    .map(|&i| self.values[i])
+                    .collect::<Vec<_>>();
+                if self.config.converged(&old_values, &values) {
+                    break;
+                }
+                // Values flowing out of the component are fixed, so the constant increment
+                // check only makes sense when nothing leaves it.
+                let diverged = if closed {
+                    self.config.diverged(&old_values, &values, self.discount)
+                } else {
+                    values.iter().any(|v| !v.is_finite())
+                };
+                if diverged {
+                    return Err(SolverError::Diverged { iterations });
+                }
+                if let Some(error) = self.config.exceeded(sweeps, start) {
+                    return Err(error(self.solution(iterations, start)));
+                }
+            }
+        }
+
+        Ok(self.solution(iterations, start))
+    }
+}